@@ -0,0 +1,118 @@
+#![feature(test)]
+
+extern crate grok;
+extern crate test;
+
+use grok::{Grok, PatternSet};
+use test::Bencher;
+
+const PATTERNS: &[&str] = &[
+    r"^%{HAPROXYHTTP}$",
+    r"^%{HAPROXYTCP}$",
+    r"^%{HTTPD_COMBINEDLOG}$",
+    r"^%{HTTPD_COMMONLOG}$",
+    r"^%{HTTPD_ERRORLOG}$",
+    r"^%{CRONLOG}$",
+    r"^%{SYSLOG5424LINE}$",
+    r"^%{SYSLOGLINE}$",
+];
+
+fn sequential() -> PatternSet {
+    Grok::default()
+        .compile_set(PATTERNS, true)
+        .expect("Error while compiling!")
+}
+
+fn single_pass() -> PatternSet {
+    Grok::default()
+        .compile_set_single_pass(PATTERNS, true)
+        .expect("Error while compiling!")
+}
+
+fn bench(b: &mut Bencher, set: PatternSet, msg: &str) {
+    b.iter(|| {
+        if let Some(found) = set.match_first(msg) {
+            test::black_box(&found);
+        }
+    });
+}
+
+const LAST_MATCH: &str =
+    "Sep 19 18:19:00 web01 sshd[1234]: Accepted publickey for root from 8.8.8.8";
+const FIRST_MATCH: &str = r#"Sep 19 18:19:00 lb01 haproxy[1234]: 8.8.8.8:54321 [19/Sep/2016:18:19:00.123] http-in static/srv1 10/0/30/69/109 200 2750 - - ---- 1/1/0/0/0 0/0 "GET /index.html HTTP/1.1""#;
+const NO_MATCH: &str = "2016-09-19T18:19:00 [8.8.8.8:prd] DEBUG this is an example log message";
+
+#[bench]
+fn bench_set_sequential_first_match(b: &mut Bencher) {
+    bench(b, sequential(), FIRST_MATCH);
+}
+
+#[bench]
+fn bench_set_single_pass_first_match(b: &mut Bencher) {
+    bench(b, single_pass(), FIRST_MATCH);
+}
+
+#[bench]
+fn bench_set_sequential_last_match(b: &mut Bencher) {
+    bench(b, sequential(), LAST_MATCH);
+}
+
+#[bench]
+fn bench_set_single_pass_last_match(b: &mut Bencher) {
+    bench(b, single_pass(), LAST_MATCH);
+}
+
+#[bench]
+fn bench_set_sequential_no_match(b: &mut Bencher) {
+    bench(b, sequential(), NO_MATCH);
+}
+
+#[bench]
+fn bench_set_single_pass_no_match(b: &mut Bencher) {
+    bench(b, single_pass(), NO_MATCH);
+}
+
+/// Patterns whose prefilters pass for most lines, so they end up merged into one regex.
+const UNFILTERED_PATTERNS: &[&str] = &[
+    r"^%{IPV4:ip} %{WORD:verb} %{INT:status}",
+    r"^%{HOSTNAME:host} %{INT:port}",
+    r"^%{IPV6:ip} %{WORD:verb}",
+    r"^%{INT:a} %{INT:b} %{INT:c}",
+    r"^%{NUMBER:value} %{WORD:unit}",
+    r"^%{WORD:a} %{WORD:b} %{WORD:c} %{WORD:d}",
+];
+
+const UNFILTERED_LAST_MATCH: &str = "alpha beta gamma delta";
+const UNFILTERED_NO_MATCH: &str = "alpha! beta? gamma. delta,";
+
+fn unfiltered_sequential() -> PatternSet {
+    Grok::default()
+        .compile_set(UNFILTERED_PATTERNS, true)
+        .expect("Error while compiling!")
+}
+
+fn unfiltered_single_pass() -> PatternSet {
+    Grok::default()
+        .compile_set_single_pass(UNFILTERED_PATTERNS, true)
+        .expect("Error while compiling!")
+}
+
+#[bench]
+fn bench_set_unfiltered_sequential_last_match(b: &mut Bencher) {
+    bench(b, unfiltered_sequential(), UNFILTERED_LAST_MATCH);
+}
+
+#[bench]
+fn bench_set_unfiltered_single_pass_last_match(b: &mut Bencher) {
+    bench(b, unfiltered_single_pass(), UNFILTERED_LAST_MATCH);
+}
+
+#[bench]
+fn bench_set_unfiltered_sequential_no_match(b: &mut Bencher) {
+    bench(b, unfiltered_sequential(), UNFILTERED_NO_MATCH);
+}
+
+#[bench]
+fn bench_set_unfiltered_single_pass_no_match(b: &mut Bencher) {
+    bench(b, unfiltered_single_pass(), UNFILTERED_NO_MATCH);
+}
@@ -1,14 +1,71 @@
 extern crate glob;
 
+#[path = "src/pattern_file.rs"]
+mod pattern_file;
+
 use glob::glob;
+use std::collections::{BTreeMap, BTreeSet};
 use std::env;
 use std::fmt;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::prelude::*;
 use std::io::BufReader;
 use std::path::Path;
 
+/// A pattern file, which can be included through its `patterns-<name>` feature.
+struct Pack {
+    name: String,
+    enabled: bool,
+    patterns: Vec<(String, String)>,
+    /// The overrides from `patterns/ecs-v1` with field names of the Elastic Common Schema.
+    ecs_v1: Vec<(String, String)>,
+}
+
 fn main() {
+    let manifest = fs::read_to_string("Cargo.toml").unwrap();
+
+    let mut packs = Vec::new();
+    for path in glob("patterns/*.pattern").unwrap().map(|e| e.unwrap()) {
+        let name = path.file_stem().unwrap().to_str().unwrap().to_string();
+        let feature = format!("patterns-{}", name);
+        if !manifest.contains(&format!("\n{} = ", feature)) {
+            panic!(
+                "{} has no `{}` feature, add it to Cargo.toml and to `default-patterns`",
+                path.display(),
+                feature
+            );
+        }
+        let enabled = env::var_os(format!(
+            "CARGO_FEATURE_{}",
+            feature.to_uppercase().replace('-', "_")
+        ))
+        .is_some();
+
+        packs.push(Pack {
+            name,
+            enabled,
+            patterns: read_patterns(&path),
+            ecs_v1: Vec::new(),
+        });
+    }
+
+    for path in glob("patterns/ecs-v1/*.pattern")
+        .unwrap()
+        .map(|e| e.unwrap())
+    {
+        let name = path.file_stem().unwrap().to_str().unwrap();
+        match packs.iter_mut().find(|p| p.name == name) {
+            Some(pack) => pack.ecs_v1 = read_patterns(&path),
+            None => panic!(
+                "{} overrides patterns/{}.pattern which does not exist",
+                path.display(),
+                name
+            ),
+        }
+    }
+
+    check_references(&packs);
+
     let mut output = String::new();
 
     fmt::write(
@@ -17,32 +74,245 @@ fn main() {
     )
     .unwrap();
 
-    for line in glob("patterns/*.pattern")
-        .unwrap() // load filepaths
-        // extract the filepath
-        .map(|e| e.unwrap())
-        // open file for path
-        .map(|path| File::open(path).unwrap())
-        // flatten to actual lines
-        .flat_map(|f| BufReader::new(f).lines())
-        .map(|line| line.unwrap())
-        // filter comments
-        .filter(|line| !line.starts_with('#'))
-        // filter empty lines
-        .filter(|line| !line.is_empty())
-    {
-        let (key, value) = line.split_at(line.find(' ').unwrap());
-        fmt::write(
-            &mut output,
-            format_args!("\t(\"{}\", r#\"{}\"#),\n", key, &value[1..]),
-        )
-        .unwrap();
+    // The patterns of every pack are contiguous, so each pack maps to a range.
+    let mut ranges = Vec::new();
+    for pack in &packs {
+        let start = ranges.last().map_or(0, |&(_, end)| end);
+        let mut end = start;
+        if pack.enabled {
+            for (key, value) in &pack.patterns {
+                fmt::write(
+                    &mut output,
+                    format_args!("\t(\"{}\", r#\"{}\"#),\n", key, value),
+                )
+                .unwrap();
+                end += 1;
+            }
+        }
+        ranges.push((start, end));
     }
 
     fmt::write(&mut output, format_args!("];\n")).unwrap();
 
+    fmt::write(
+        &mut output,
+        format_args!("static ECS_V1_PATTERNS: &[(&str, &str)] = &[\n"),
+    )
+    .unwrap();
+    for pack in packs.iter().filter(|p| p.enabled) {
+        for (key, value) in &pack.ecs_v1 {
+            fmt::write(
+                &mut output,
+                format_args!("\t(\"{}\", r#\"{}\"#),\n", key, value),
+            )
+            .unwrap();
+        }
+    }
+    fmt::write(&mut output, format_args!("];\n")).unwrap();
+
+    write_pack_enum(&mut output, &packs, &ranges);
+
     let out_dir = env::var("OUT_DIR").unwrap();
     let dest_path = Path::new(&out_dir).join("default_patterns.rs");
     let mut file = File::create(&dest_path).unwrap();
     file.write_all(output.as_bytes()).unwrap();
 }
+
+/// Reads all patterns of a pattern file, failing the build with the line number if one
+/// is malformed.
+fn read_patterns(path: &Path) -> Vec<(String, String)> {
+    let file = File::open(path).unwrap();
+    let mut patterns = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.unwrap();
+        match pattern_file::parse_line(&line) {
+            Ok(Some((key, value))) => patterns.push((key.to_string(), value.to_string())),
+            Ok(None) => {}
+            Err(reason) => panic!("{}:{}: {}", path.display(), index + 1, reason),
+        }
+    }
+    patterns
+}
+
+/// Writes the `Pack` enum with one variant per pattern file.
+fn write_pack_enum(output: &mut String, packs: &[Pack], ranges: &[(usize, usize)]) {
+    let variants = packs
+        .iter()
+        .map(|p| {
+            p.name
+                .split(|c: char| !c.is_ascii_alphanumeric())
+                .map(|part| {
+                    let mut chars = part.chars();
+                    match chars.next() {
+                        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                        None => String::new(),
+                    }
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>();
+
+    output.push_str(
+        "/// A file of default patterns, which is only embedded if its `patterns-<file>` feature
+/// is enabled.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[non_exhaustive]
+pub enum Pack {\n",
+    );
+    for (pack, variant) in packs.iter().zip(&variants) {
+        fmt::write(
+            output,
+            format_args!(
+                "\t/// The patterns of `{}.pattern`, enabled with the `patterns-{}` feature.\n\t{},\n",
+                pack.name, pack.name, variant
+            ),
+        )
+        .unwrap();
+    }
+    output.push_str("}\n\nimpl Pack {\n");
+
+    output.push_str(
+        "\t/// Returns all packs, including the ones which are not enabled.
+\tpub fn all() -> &'static [Pack] {
+\t\t&[\n",
+    );
+    for variant in &variants {
+        fmt::write(output, format_args!("\t\t\tPack::{},\n", variant)).unwrap();
+    }
+    output.push_str("\t\t]\n\t}\n\n");
+
+    output.push_str(
+        "\t/// Returns the name of the pattern file without its extension, like `linux-syslog`.
+\tpub fn name(&self) -> &'static str {
+\t\tmatch *self {\n",
+    );
+    for (pack, variant) in packs.iter().zip(&variants) {
+        fmt::write(
+            output,
+            format_args!("\t\t\tPack::{} => \"{}\",\n", variant, pack.name),
+        )
+        .unwrap();
+    }
+    output.push_str("\t\t}\n\t}\n\n");
+
+    output.push_str(
+        "\t/// Returns true if the pack is embedded, which depends on its `patterns-<file>` feature.
+\tpub fn is_enabled(&self) -> bool {
+\t\tmatch *self {\n",
+    );
+    for (pack, variant) in packs.iter().zip(&variants) {
+        fmt::write(
+            output,
+            format_args!("\t\t\tPack::{} => {},\n", variant, pack.enabled),
+        )
+        .unwrap();
+    }
+    output.push_str("\t\t}\n\t}\n\n");
+
+    output.push_str(
+        "\t/// Returns true if the pack has a variant of its patterns in the given flavor.
+\t///
+\t/// Every pack supports `Compat::Legacy`, but only some ship an `ecs-v1` variant. The
+\t/// patterns of the other packs are left out with `Compat::EcsV1`.
+\tpub fn supports(&self, compat: Compat) -> bool {
+\t\tmatch compat {
+\t\t\tCompat::Legacy => true,
+\t\t\tCompat::EcsV1 => ",
+    );
+    let ecs_v1 = packs
+        .iter()
+        .zip(&variants)
+        .filter(|(pack, _)| !pack.ecs_v1.is_empty())
+        .map(|(_, variant)| format!("Pack::{}", variant))
+        .collect::<Vec<_>>();
+    if ecs_v1.is_empty() {
+        output.push_str("false");
+    } else {
+        fmt::write(
+            output,
+            format_args!("matches!(*self, {})", ecs_v1.join(" | ")),
+        )
+        .unwrap();
+    }
+    output.push_str(",\n\t\t}\n\t}\n\n");
+
+    output.push_str(
+        "\t/// Returns the range of `PATTERNS` holding the patterns of this pack.
+\tfn range(&self) -> std::ops::Range<usize> {
+\t\tmatch *self {\n",
+    );
+    for (variant, (start, end)) in variants.iter().zip(ranges) {
+        fmt::write(
+            output,
+            format_args!("\t\t\tPack::{} => {}..{},\n", variant, start, end),
+        )
+        .unwrap();
+    }
+    output.push_str("\t\t}\n\t}\n}\n");
+}
+
+/// Fails the build if a pattern of an enabled pack refers to a pattern which is only
+/// defined in disabled packs.
+fn check_references(packs: &[Pack]) {
+    let mut defined_in: BTreeMap<&str, Vec<&Pack>> = BTreeMap::new();
+    for pack in packs {
+        for (key, _) in &pack.patterns {
+            defined_in.entry(key.as_str()).or_default().push(pack);
+        }
+    }
+
+    // The missing references grouped by the enabled pack and the features defining them.
+    let mut missing: BTreeMap<(&str, String), BTreeSet<&str>> = BTreeMap::new();
+    for pack in packs.iter().filter(|p| p.enabled) {
+        for (_, value) in pack.patterns.iter().chain(&pack.ecs_v1) {
+            for reference in references(value) {
+                let packs = match defined_in.get(reference) {
+                    Some(packs) if !packs.iter().any(|p| p.enabled) => packs,
+                    _ => continue,
+                };
+                let features = packs
+                    .iter()
+                    .map(|p| format!("`patterns-{}`", p.name))
+                    .collect::<Vec<_>>()
+                    .join(" or ");
+                missing
+                    .entry((pack.name.as_str(), features))
+                    .or_default()
+                    .insert(reference);
+            }
+        }
+    }
+
+    let problems = missing
+        .iter()
+        .map(|((pack, features), references)| {
+            format!(
+                "  `patterns-{}` needs {} for {}",
+                pack,
+                features,
+                references.iter().copied().collect::<Vec<_>>().join(", ")
+            )
+        })
+        .collect::<Vec<_>>();
+    if !problems.is_empty() {
+        panic!(
+            "enabled pattern packs refer to patterns of disabled packs:\n{}",
+            problems.join("\n")
+        );
+    }
+}
+
+/// Returns the names of all patterns the definition refers to with `%{NAME...}`.
+fn references(definition: &str) -> Vec<&str> {
+    definition
+        .split("%{")
+        .skip(1)
+        .map(|rest| {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            &rest[..end]
+        })
+        .filter(|name| !name.is_empty())
+        .collect()
+}
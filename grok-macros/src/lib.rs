@@ -0,0 +1,301 @@
+//! Compile-time checked [grok](https://docs.rs/grok) patterns.
+//!
+//! The `grok!` macro compiles a pattern against the default patterns of the `grok` crate
+//! while your crate is being built. Unknown pattern names or a regex which fails to compile
+//! make the build fail, and every alias of the pattern turns into a field of a generated
+//! struct, so there are no more string keys to mistype.
+#![doc(html_root_url = "https://docs.rs/grok-macros/2.0.0")]
+
+use grok::Grok;
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use std::collections::BTreeMap;
+use syn::parse::{Parse, ParseStream};
+use syn::{parse_macro_input, Attribute, Ident, LitStr, Token, Visibility};
+
+/// Compiles a grok pattern at build time and generates a struct with one field per alias.
+///
+/// The pattern is compiled with the default patterns and only aliased captures (and named
+/// groups in the regex) become fields, just like `Grok::compile(pattern, true)`. Field names
+/// are derived from the capture names by turning every run of characters which are not
+/// valid in an identifier into an underscore, so `[http][verb]` becomes `http_verb`. Every
+/// field is an `Option` since a capture might not participate in a match. Its type follows
+/// the type suffix of the alias: `String` without a suffix, `i64` for `int`, `f64` for
+/// `float` and `bool` for `bool`.
+///
+/// The macro can either declare a named struct:
+///
+/// ```
+/// use grok_macros::grok;
+///
+/// grok!(pub struct AccessLog = "%{IPORHOST:clientip} %{WORD:verb} %{NUMBER:bytes:int}");
+///
+/// let pattern = AccessLog::compile().unwrap();
+/// let log = AccessLog::parse(&pattern, "127.0.0.1 GET 512").unwrap().unwrap();
+/// assert_eq!(Some("127.0.0.1".to_string()), log.clientip);
+/// assert_eq!(Some(512), log.bytes);
+/// ```
+///
+/// Or be used as an expression, which compiles the pattern and returns a value with a
+/// `match_against` method producing the (anonymous) struct:
+///
+/// ```
+/// use grok_macros::grok;
+///
+/// let pattern = grok!("%{USERNAME:user} %{INT:uid:int}").unwrap();
+/// let found = pattern.match_against("root 0").unwrap().unwrap();
+/// assert_eq!(Some("root".to_string()), found.user);
+/// assert_eq!(Some(0), found.uid);
+/// ```
+///
+/// Patterns which do not compile fail the build:
+///
+/// ```compile_fail
+/// use grok_macros::grok;
+///
+/// grok!(struct Broken = "%{IPORHOSTNAME:clientip}");
+/// ```
+///
+/// ```compile_fail
+/// use grok_macros::grok;
+///
+/// grok!(struct Broken = "%{WORD:verb} (unclosed");
+/// ```
+///
+/// The pattern is checked against the `grok` crate the macro itself is built with, which
+/// has all the default patterns. Your crate may use `grok` with fewer `patterns-<file>`
+/// features though, since cargo builds the dependencies of proc macros separately. A
+/// pattern which refers to a pattern file that is not enabled at runtime passes the check
+/// but fails to compile at runtime, so `compile` (and the expression form) return a
+/// `Result` instead of panicking.
+#[proc_macro]
+pub fn grok(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as GrokInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// The parsed macro input, either `[attrs] [vis] struct Name = "pattern"` or `"pattern"`.
+struct GrokInput {
+    item: Option<(Vec<Attribute>, Visibility, Ident)>,
+    pattern: LitStr,
+}
+
+impl Parse for GrokInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(LitStr) {
+            let pattern = input.parse()?;
+            return Ok(GrokInput {
+                item: None,
+                pattern,
+            });
+        }
+
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        input.parse::<Token![struct]>()?;
+        let name = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let pattern = input.parse()?;
+        if input.peek(Token![;]) {
+            input.parse::<Token![;]>()?;
+        }
+        Ok(GrokInput {
+            item: Some((attrs, vis, name)),
+            pattern,
+        })
+    }
+}
+
+/// A field of the generated struct.
+struct Field {
+    capture: String,
+    ident: Ident,
+    conversion: Option<String>,
+}
+
+fn expand(input: GrokInput) -> syn::Result<TokenStream2> {
+    let pattern_str = input.pattern.value();
+    let pattern = Grok::with_default_patterns()
+        .compile(&pattern_str, true)
+        .map_err(|e| syn::Error::new(input.pattern.span(), e))?;
+
+    let mut fields: BTreeMap<String, Field> = BTreeMap::new();
+    for capture in pattern.capture_names() {
+        let ident = field_ident(capture).ok_or_else(|| {
+            syn::Error::new(
+                input.pattern.span(),
+                format!(
+                    "the capture name \"{}\" cannot be used as a field name",
+                    capture
+                ),
+            )
+        })?;
+        if let Some(other) = fields.get(&ident.to_string()) {
+            return Err(syn::Error::new(
+                input.pattern.span(),
+                format!(
+                    "the captures \"{}\" and \"{}\" both map to the field `{}`",
+                    other.capture, capture, ident
+                ),
+            ));
+        }
+        let conversion = pattern.capture_conversion(capture).map(String::from);
+        fields.insert(
+            ident.to_string(),
+            Field {
+                capture: capture.into(),
+                ident,
+                conversion,
+            },
+        );
+    }
+
+    let mut declarations = Vec::new();
+    let mut extractions = Vec::new();
+    for field in fields.values() {
+        let ident = &field.ident;
+        let capture = &field.capture;
+        let (ty, extraction) = match field.conversion.as_deref() {
+            None => (
+                quote!(::std::string::String),
+                quote!(matches.get(#capture).map(::std::string::String::from)),
+            ),
+            Some(conversion) => {
+                let (ty, variant) = match conversion {
+                    "int" => (quote!(i64), quote!(Int)),
+                    "float" => (quote!(f64), quote!(Float)),
+                    "bool" => (quote!(bool), quote!(Bool)),
+                    other => {
+                        return Err(syn::Error::new(
+                            input.pattern.span(),
+                            format!("the converter \"{}\" is not supported by grok!", other),
+                        ))
+                    }
+                };
+                let extraction = quote! {
+                    match matches.get_typed(#capture) {
+                        ::std::option::Option::Some(::std::result::Result::Ok(::grok::Value::#variant(v))) => {
+                            ::std::option::Option::Some(v)
+                        }
+                        ::std::option::Option::Some(::std::result::Result::Err(e)) => {
+                            return ::std::result::Result::Err(e)
+                        }
+                        _ => ::std::option::Option::None,
+                    }
+                };
+                (ty, extraction)
+            }
+        };
+        let doc = format!("The value of the `{}` capture.", capture);
+        declarations.push(quote! {
+            #[doc = #doc]
+            pub #ident: ::std::option::Option<#ty>
+        });
+        extractions.push(quote!(#ident: #extraction));
+    }
+
+    let (attrs, vis, name, is_item) = match input.item {
+        Some((attrs, vis, name)) => (attrs, vis, name, true),
+        None => (
+            Vec::new(),
+            Visibility::Inherited,
+            Ident::new("GrokMatch", Span::call_site()),
+            false,
+        ),
+    };
+
+    let definition = quote! {
+        #(#attrs)*
+        #[derive(Clone, Debug, PartialEq)]
+        #vis struct #name {
+            #(#declarations,)*
+        }
+
+        impl #name {
+            /// The grok pattern this struct has been generated from.
+            pub const PATTERN: &'static str = #pattern_str;
+
+            /// Compiles the pattern with the default patterns.
+            ///
+            /// This only fails if a pattern file the pattern refers to is not enabled
+            /// through the `patterns-<file>` features of the `grok` crate.
+            pub fn compile() -> ::std::result::Result<::grok::Pattern, ::grok::Error> {
+                ::grok::Grok::with_default_patterns().compile(Self::PATTERN, true)
+            }
+
+            /// Extracts the fields from the matches of the compiled pattern.
+            pub fn from_matches(
+                matches: &::grok::Matches<'_>,
+            ) -> ::std::result::Result<Self, ::grok::Error> {
+                ::std::result::Result::Ok(#name {
+                    #(#extractions,)*
+                })
+            }
+
+            /// Matches the compiled pattern against the text and extracts the fields.
+            pub fn parse(
+                pattern: &::grok::Pattern,
+                text: &str,
+            ) -> ::std::option::Option<::std::result::Result<Self, ::grok::Error>> {
+                pattern
+                    .match_against(text)
+                    .map(|matches| Self::from_matches(&matches))
+            }
+        }
+    };
+
+    if is_item {
+        return Ok(definition);
+    }
+
+    let wrapper = format_ident!("GrokPattern");
+    Ok(quote! {
+        {
+            #definition
+
+            struct #wrapper(::grok::Pattern);
+
+            impl #wrapper {
+                fn match_against(
+                    &self,
+                    text: &str,
+                ) -> ::std::option::Option<::std::result::Result<#name, ::grok::Error>> {
+                    #name::parse(&self.0, text)
+                }
+            }
+
+            #name::compile().map(#wrapper)
+        }
+    })
+}
+
+/// Turns a capture name into a field identifier, or `None` if nothing usable is left.
+fn field_ident(capture: &str) -> Option<Ident> {
+    let name = capture
+        .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+        .to_ascii_lowercase();
+    if name.is_empty() {
+        return None;
+    }
+    let name = if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", name)
+    } else {
+        name
+    };
+    match syn::parse_str::<Ident>(&name) {
+        Ok(ident) => Some(ident),
+        Err(_) => match name.as_str() {
+            "self" | "super" | "crate" | "_" => {
+                Some(Ident::new(&format!("{}_", name), Span::call_site()))
+            }
+            _ => Some(Ident::new_raw(&name, Span::call_site())),
+        },
+    }
+}
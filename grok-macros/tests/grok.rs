@@ -0,0 +1,65 @@
+use grok_macros::grok;
+
+grok!(
+    /// An access log line.
+    pub struct AccessLog = "%{IPORHOST:clientip} %{WORD:verb} (?:%{NUMBER:bytes:int}|-) %{NUMBER:duration:float}"
+);
+
+grok!(struct Nested = "%{WORD:[http][verb]} %{INT:SrcPort:int} %{WORD:type}");
+grok!(struct Dashed = "%{INT:src-port:int} %{WORD:[user][name]}");
+
+#[test]
+fn test_item_struct() {
+    let pattern = AccessLog::compile().expect("Error while compiling!");
+
+    let log = AccessLog::parse(&pattern, "example.org GET 512 0.25")
+        .expect("No matches found!")
+        .expect("Conversion failed!");
+    assert_eq!(
+        AccessLog {
+            clientip: Some("example.org".into()),
+            verb: Some("GET".into()),
+            bytes: Some(512),
+            duration: Some(0.25),
+        },
+        log
+    );
+
+    let log = AccessLog::parse(&pattern, "example.org GET - 0.25")
+        .expect("No matches found!")
+        .expect("Conversion failed!");
+    assert_eq!(None, log.bytes);
+    assert!(AccessLog::parse(&pattern, "nothing to see").is_none());
+}
+
+#[test]
+fn test_sanitized_field_names() {
+    let pattern = Nested::compile().expect("Error while compiling!");
+    let nested = Nested::parse(&pattern, "GET 8080 plain")
+        .expect("No matches found!")
+        .expect("Conversion failed!");
+    assert_eq!(Some("GET".to_string()), nested.http_verb);
+    assert_eq!(Some(8080), nested.srcport);
+    assert_eq!(Some("plain".to_string()), nested.r#type);
+
+    let pattern = Dashed::compile().expect("Error while compiling!");
+    let dashed = Dashed::parse(&pattern, "8080 root")
+        .expect("No matches found!")
+        .expect("Conversion failed!");
+    assert_eq!(Some(8080), dashed.src_port);
+    assert_eq!(Some("root".to_string()), dashed.user_name);
+}
+
+#[test]
+fn test_expression() {
+    let pattern = grok!("%{USERNAME:user} %{INT:uid:int}").expect("Error while compiling!");
+    let found = pattern.match_against("root abc").map(|r| r.is_err());
+    assert_eq!(None, found);
+
+    let found = pattern
+        .match_against("root 0")
+        .expect("No matches found!")
+        .expect("Conversion failed!");
+    assert_eq!(Some("root".to_string()), found.user);
+    assert_eq!(Some(0), found.uid);
+}
@@ -0,0 +1,335 @@
+use crate::ser::ordered_fields;
+use crate::{typed_value, Error, Matches, Value};
+use serde::de::value::SeqDeserializer;
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, Deserializer, IntoDeserializer, MapAccess, Visitor,
+};
+use std::fmt;
+use std::vec::IntoIter;
+
+impl<'a> Matches<'a> {
+    /// Deserializes the matches into a user provided type, usually a struct with one
+    /// field per capture name.
+    ///
+    /// Fields which did not participate in the match are left out, so they map to
+    /// `None` for `Option` fields. Values are parsed from the matched text into the
+    /// requested type, or taken from the converted value if the alias has a type suffix.
+    /// Errors name the capture which failed to deserialize.
+    ///
+    /// If the pattern has been compiled with `CompileOptions::collect_repeated`, fields
+    /// which are captured more than once are deserialized as sequences of all their
+    /// values, like `Vec<String>`, matching the arrays emitted by `Serialize`.
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        T::deserialize(MatchesDeserializer {
+            matches: self,
+            fields: ordered_fields(self).into_iter(),
+        })
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::DeserializationFailed {
+            field: None,
+            message: msg.to_string(),
+        }
+    }
+}
+
+/// Attaches the field name to an error which has been raised while deserializing it.
+fn with_field(error: Error, name: &str) -> Error {
+    match error {
+        Error::DeserializationFailed {
+            field: None,
+            message,
+        } => Error::DeserializationFailed {
+            field: Some(name.into()),
+            message,
+        },
+        e => e,
+    }
+}
+
+/// Deserializes all participating fields of the matches as a map.
+struct MatchesDeserializer<'a> {
+    matches: &'a Matches<'a>,
+    fields: IntoIter<(&'a str, &'a str)>,
+}
+
+impl<'de, 'a> Deserializer<'de> for MatchesDeserializer<'a> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_map(FieldsAccess {
+            matches: self.matches,
+            fields: self.fields,
+            value: None,
+        })
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+/// Walks over the fields of the matches, handing out names as keys.
+struct FieldsAccess<'a> {
+    matches: &'a Matches<'a>,
+    fields: IntoIter<(&'a str, &'a str)>,
+    value: Option<(&'a str, &'a str)>,
+}
+
+impl<'de, 'a> MapAccess<'de> for FieldsAccess<'a> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        match self.fields.next() {
+            Some((name, value)) => {
+                self.value = Some((name, value));
+                seed.deserialize(name.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (name, raw) = match self.value.take() {
+            Some(field) => field,
+            None => return Err(de::Error::custom("value requested before key")),
+        };
+        let matches = self.matches;
+        if matches.collect_repeated && matches.names[name].len() > 1 {
+            let values = matches
+                .get_all(name)
+                .into_iter()
+                .map(|raw| typed_value(matches.types, name, raw))
+                .collect::<Result<Vec<_>, _>>()?;
+            return seed
+                .deserialize(RepeatedDeserializer { values })
+                .map_err(|e| with_field(e, name));
+        }
+        let value = typed_value(matches.types, name, raw)?;
+        seed.deserialize(FieldDeserializer { value })
+            .map_err(|e| with_field(e, name))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.fields.len())
+    }
+}
+
+/// Deserializes all values of a repeated field as a sequence.
+struct RepeatedDeserializer<'a> {
+    values: Vec<Value<'a>>,
+}
+
+impl<'de, 'a> Deserializer<'de> for RepeatedDeserializer<'a> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let values = self
+            .values
+            .into_iter()
+            .map(|value| FieldDeserializer { value });
+        SeqDeserializer::new(values).deserialize_any(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct enum
+        identifier ignored_any
+    }
+}
+
+/// Deserializes a single (typed) value, parsing the matched text if needed.
+struct FieldDeserializer<'a> {
+    value: Value<'a>,
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                match self.value {
+                    Value::Str(s) => match s.parse() {
+                        Ok(v) => visitor.$visit(v),
+                        Err(e) => Err(de::Error::custom(format_args!(
+                            "invalid value \"{}\": {}",
+                            s, e
+                        ))),
+                    },
+                    _ => self.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> IntoDeserializer<'de, Error> for FieldDeserializer<'a> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de, 'a> Deserializer<'de> for FieldDeserializer<'a> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            Value::Str(s) => visitor.visit_str(s),
+            Value::Int(i) => visitor.visit_i64(i),
+            Value::Float(f) => visitor.visit_f64(f),
+            Value::Bool(b) => visitor.visit_bool(b),
+            Value::Custom(c) => visitor.visit_string(c.to_string()),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_enum(self.value.to_string().into_deserializer())
+    }
+
+    serde::forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct seq tuple tuple_struct map
+        struct identifier ignored_any
+    }
+}
+
+#[cfg(all(
+    test,
+    feature = "default-patterns",
+    any(feature = "onig", feature = "fancy-regex")
+))]
+mod tests {
+    use crate::{Error, Grok};
+    use serde::Deserialize;
+    use std::net::IpAddr;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct AccessLog {
+        clientip: IpAddr,
+        verb: String,
+        response: u16,
+        bytes: Option<u64>,
+    }
+
+    #[test]
+    fn test_deserialize_matches() {
+        let mut grok = Grok::default();
+        let pattern = grok
+            .compile(
+                "%{IP:clientip} %{WORD:verb} %{INT:response:int} (?:%{INT:bytes}|-)",
+                true,
+            )
+            .expect("Error while compiling!");
+
+        let matches = pattern
+            .match_against("10.0.0.1 GET 200 512")
+            .expect("No matches found!");
+        assert_eq!(
+            AccessLog {
+                clientip: IpAddr::from([10, 0, 0, 1]),
+                verb: "GET".into(),
+                response: 200,
+                bytes: Some(512),
+            },
+            matches.deserialize().unwrap()
+        );
+
+        let matches = pattern
+            .match_against("10.0.0.1 GET 304 -")
+            .expect("No matches found!");
+        let log = matches.deserialize::<AccessLog>().unwrap();
+        assert_eq!(304, log.response);
+        assert_eq!(None, log.bytes);
+    }
+
+    #[test]
+    fn test_deserialize_failure_names_capture() {
+        let mut grok = Grok::default();
+        let pattern = grok
+            .compile(
+                "%{WORD:clientip} %{WORD:verb} %{INT:response} %{INT:bytes}",
+                true,
+            )
+            .expect("Error while compiling!");
+
+        let matches = pattern
+            .match_against("localhost GET 200 512")
+            .expect("No matches found!");
+        match matches.deserialize::<AccessLog>() {
+            Err(Error::DeserializationFailed { field, .. }) => {
+                assert_eq!(Some("clientip".into()), field)
+            }
+            other => panic!("{:?}", other),
+        }
+
+        let pattern = grok
+            .compile(
+                "%{IP:clientip} %{WORD:verb} %{INT:response} %{INT:bytes}",
+                true,
+            )
+            .expect("Error while compiling!");
+        let matches = pattern
+            .match_against("127.0.0.1 GET 99999 512")
+            .expect("No matches found!");
+        match matches.deserialize::<AccessLog>() {
+            Err(Error::DeserializationFailed { field, .. }) => {
+                assert_eq!(Some("response".into()), field)
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+}
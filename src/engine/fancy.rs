@@ -0,0 +1,63 @@
+use super::{Construct, Engine, EngineError, Flags, Locations};
+use fancy_regex::RegexBuilder;
+
+/// The inline flag which enables multi-line mode for the whole regex.
+const MULTI_LINE: &str = "(?m)";
+
+/// The `fancy-regex` engine, a pure Rust backtracking engine which supports lookaround,
+/// atomic groups and backreferences on top of the `regex` crate.
+#[derive(Debug)]
+pub(crate) struct Regex(fancy_regex::Regex);
+
+impl Engine for Regex {
+    const NAME: &'static str = "fancy-regex";
+    const UNSUPPORTED: &'static [Construct] = &[Construct::NestedQuantifier];
+    const BACKTRACKING: bool = true;
+
+    fn compile(regex: &str, flags: Flags) -> Result<Self, EngineError> {
+        // Like Oniguruma with the Ruby syntax, `^` and `$` match at line boundaries and
+        // unnamed groups do not capture as soon as there is a named one. Multi-line mode
+        // is enabled inline, because with `RegexBuilder::multi_line` the parts handed to
+        // the `regex` crate turn `\A` and `\z` into line anchors as well.
+        RegexBuilder::new(&format!("{}{}", MULTI_LINE, regex))
+            .case_insensitive(flags.case_insensitive)
+            .ignore_whitespace(flags.ignore_whitespace)
+            .dot_matches_new_line(flags.dot_matches_new_line)
+            .oniguruma_mode(true)
+            .ignore_numbered_groups_when_named_groups_exist(true)
+            .build()
+            .map(Regex)
+            .map_err(|e| {
+                let position = match e {
+                    fancy_regex::Error::ParseError(position, _) => {
+                        Some(position.saturating_sub(MULTI_LINE.len()))
+                    }
+                    _ => None,
+                };
+                EngineError::new(Self::NAME, e.to_string(), None, position)
+            })
+    }
+
+    fn captures_at(&self, text: &str, start: usize) -> Option<Locations> {
+        // A runtime error (like exceeding the backtrack limit) is treated as no match,
+        // just like Oniguruma does.
+        let captures = self.0.captures_from_pos(text, start).ok()??;
+        Some(
+            (0..captures.len())
+                .map(|i| captures.get(i).map(|m| (m.start(), m.end())))
+                .collect(),
+        )
+    }
+
+    fn capture_names(&self) -> Vec<(String, Vec<u32>)> {
+        self.0
+            .capture_names()
+            .enumerate()
+            .filter_map(|(i, name)| name.map(|name| (name.to_string(), vec![i as u32])))
+            .collect()
+    }
+
+    fn captures_len(&self) -> usize {
+        self.0.captures_len() - 1
+    }
+}
@@ -0,0 +1,387 @@
+//! The regex engines a `Pattern` can be compiled with.
+//!
+//! Exactly one engine is used, selected through cargo features. If several are enabled the
+//! first one of `onig`, `fancy-regex` and `regex` wins, so enabling the default features
+//! always keeps the full Oniguruma compatibility.
+
+#[cfg(feature = "onig")]
+mod onig;
+#[cfg(feature = "onig")]
+pub(crate) use self::onig::Regex;
+
+#[cfg(all(feature = "fancy-regex", not(feature = "onig")))]
+mod fancy;
+#[cfg(all(feature = "fancy-regex", not(feature = "onig")))]
+pub(crate) use self::fancy::Regex;
+
+#[cfg(all(feature = "regex", not(any(feature = "onig", feature = "fancy-regex"))))]
+mod regex;
+#[cfg(all(feature = "regex", not(any(feature = "onig", feature = "fancy-regex"))))]
+pub(crate) use self::regex::Regex;
+
+#[cfg(not(any(feature = "onig", feature = "fancy-regex", feature = "regex")))]
+compile_error!(
+    "grok needs a regex engine, enable one of the `onig`, `fancy-regex` or `regex` features"
+);
+
+use crate::Error;
+use std::error::Error as StdError;
+use std::fmt;
+
+/// The byte offsets of every capture group after a match, where index 0 is the whole
+/// match and groups which did not participate are `None`.
+pub(crate) type Locations = Vec<Option<(usize, usize)>>;
+
+/// The interface every regex engine implements.
+pub(crate) trait Engine: Sized {
+    /// The name of the engine, which is also the name of its cargo feature.
+    const NAME: &'static str;
+
+    /// The regex constructs this engine cannot handle.
+    const UNSUPPORTED: &'static [Construct];
+
+    /// Whether the engine backtracks, so the branches of an alternation in an atomic
+    /// group are tried strictly one after another.
+    const BACKTRACKING: bool;
+
+    /// Compiles the regex with the given flags, returning the error reported by the engine
+    /// if it fails.
+    fn compile(regex: &str, flags: Flags) -> Result<Self, EngineError>;
+
+    /// Matches the regex against the text and returns the locations of all groups.
+    fn captures(&self, text: &str) -> Option<Locations> {
+        self.captures_at(text, 0)
+    }
+
+    /// Matches the regex against the text, starting the search at the given byte offset,
+    /// and returns the locations of all groups.
+    ///
+    /// Unlike matching against `&text[start..]`, lookbehinds and anchors like `\b` still
+    /// see the text before the offset.
+    fn captures_at(&self, text: &str, start: usize) -> Option<Locations>;
+
+    /// Returns the name of every named group together with its group indices.
+    fn capture_names(&self) -> Vec<(String, Vec<u32>)>;
+
+    /// Returns the number of capture groups, not counting the whole match.
+    ///
+    /// Like in Oniguruma, unnamed groups are not counted if the regex has named groups.
+    fn captures_len(&self) -> usize;
+}
+
+/// The flags a regex is compiled with, which every engine supports.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct Flags {
+    pub(crate) case_insensitive: bool,
+    pub(crate) ignore_whitespace: bool,
+    pub(crate) dot_matches_new_line: bool,
+}
+
+/// Compiles the regex with the selected engine, naming the first construct it does not
+/// support if there is one.
+pub(crate) fn compile(regex: &str, flags: Flags) -> Result<Regex, Error> {
+    if let Some(construct) = find_construct(regex, Regex::UNSUPPORTED) {
+        return Err(Error::UnsupportedRegexConstruct {
+            engine: Regex::NAME.into(),
+            construct: construct.to_string(),
+            regex: regex.into(),
+        });
+    }
+    Regex::compile(regex, flags).map_err(|source| Error::RegexCompilationFailed {
+        regex: regex.into(),
+        reference: None,
+        pattern: None,
+        source: Box::new(source),
+    })
+}
+
+/// The error a regex engine reported when compiling a regex, see
+/// `Error::RegexCompilationFailed`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EngineError {
+    engine: &'static str,
+    message: String,
+    code: Option<i32>,
+    position: Option<usize>,
+}
+
+impl EngineError {
+    /// Creates the error of the given engine.
+    pub(crate) fn new(
+        engine: &'static str,
+        message: String,
+        code: Option<i32>,
+        position: Option<usize>,
+    ) -> Self {
+        EngineError {
+            engine,
+            message,
+            code,
+            position,
+        }
+    }
+
+    /// Returns the name of the regex engine, like `onig`.
+    pub fn engine(&self) -> &str {
+        self.engine
+    }
+
+    /// Returns the message of the regex engine.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the error code, if the engine has one (like Oniguruma).
+    pub fn code(&self) -> Option<i32> {
+        self.code
+    }
+
+    /// Returns the byte offset in the regex the engine failed at, if it reports one.
+    pub fn position(&self) -> Option<usize> {
+        self.position
+    }
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(code) = self.code {
+            write!(f, " (code {})", code)?;
+        }
+        if let Some(position) = self.position {
+            write!(f, " at byte {}", position)?;
+        }
+        Ok(())
+    }
+}
+
+impl StdError for EngineError {}
+
+/// A regex construct which is not supported by every engine.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Construct {
+    Lookahead,
+    NegativeLookahead,
+    Lookbehind,
+    NegativeLookbehind,
+    AtomicGroup,
+    Conditional,
+    AbsentOperator,
+    Backreference,
+    SubroutineCall,
+    PossessiveQuantifier,
+    NestedQuantifier,
+    KeepOut,
+    ContinueAnchor,
+    HexDigit,
+    BareBrace,
+}
+
+impl std::fmt::Display for Construct {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match *self {
+            Construct::Lookahead => "lookahead `(?=...)`",
+            Construct::NegativeLookahead => "negative lookahead `(?!...)`",
+            Construct::Lookbehind => "lookbehind `(?<=...)`",
+            Construct::NegativeLookbehind => "negative lookbehind `(?<!...)`",
+            Construct::AtomicGroup => "atomic group `(?>...)`",
+            Construct::Conditional => "conditional `(?(cond)...)`",
+            Construct::AbsentOperator => "absent operator `(?~...)`",
+            Construct::Backreference => "backreference `\\1` / `\\k<name>`",
+            Construct::SubroutineCall => "subroutine call `\\g<name>`",
+            Construct::PossessiveQuantifier => "possessive quantifier `*+` / `++` / `?+`",
+            Construct::NestedQuantifier => "nested quantifier like `.?*`",
+            Construct::KeepOut => "keep out `\\K`",
+            Construct::ContinueAnchor => "anchor `\\G`",
+            Construct::HexDigit => "hex digit class `\\h` / `\\H`",
+            Construct::BareBrace => "literal `{` outside a repetition `{n,m}`",
+        })
+    }
+}
+
+/// Returns the first construct of the given list which is used by the regex.
+///
+/// Character classes and escaped characters are skipped, so only constructs which would
+/// actually be interpreted by the engine are found.
+fn find_construct(regex: &str, constructs: &[Construct]) -> Option<Construct> {
+    if constructs.is_empty() {
+        return None;
+    }
+    let chars = regex.chars().collect::<Vec<_>>();
+    let at = |i: usize| chars.get(i).copied();
+    let mut pos = 0;
+    while let Some(c) = at(pos) {
+        let found = match c {
+            '\\' => {
+                let found = match at(pos + 1) {
+                    Some('1'..='9') | Some('k') => Some(Construct::Backreference),
+                    Some('g') => Some(Construct::SubroutineCall),
+                    Some('K') => Some(Construct::KeepOut),
+                    Some('G') => Some(Construct::ContinueAnchor),
+                    Some('h') | Some('H') => Some(Construct::HexDigit),
+                    _ => None,
+                };
+                pos += 2;
+                found
+            }
+            '[' => {
+                pos = skip_class(&chars, pos);
+                None
+            }
+            '(' if at(pos + 1) == Some('?') => {
+                let found = match (at(pos + 2), at(pos + 3)) {
+                    (Some('='), _) => Some(Construct::Lookahead),
+                    (Some('!'), _) => Some(Construct::NegativeLookahead),
+                    (Some('<'), Some('=')) => Some(Construct::Lookbehind),
+                    (Some('<'), Some('!')) => Some(Construct::NegativeLookbehind),
+                    (Some('>'), _) => Some(Construct::AtomicGroup),
+                    (Some('('), _) => Some(Construct::Conditional),
+                    (Some('~'), _) => Some(Construct::AbsentOperator),
+                    _ => None,
+                };
+                pos += 2;
+                found
+            }
+            '*' | '+' | '?' if at(pos + 1) == Some('+') => {
+                pos += 2;
+                Some(Construct::PossessiveQuantifier)
+            }
+            '*' | '+' | '?' if at(pos + 1) == Some('*') => {
+                pos += 2;
+                Some(Construct::NestedQuantifier)
+            }
+            '{' => {
+                let found = if is_repetition(&chars, pos) {
+                    None
+                } else {
+                    Some(Construct::BareBrace)
+                };
+                pos += 1;
+                found
+            }
+            _ => {
+                pos += 1;
+                None
+            }
+        };
+        if let Some(construct) = found.filter(|c| constructs.contains(c)) {
+            return Some(construct);
+        }
+    }
+    None
+}
+
+/// Returns true if the `{` at `start` begins a counted repetition like `{2}`, `{2,}` or
+/// `{2,5}` of the preceding item, rather than standing for itself.
+fn is_repetition(chars: &[char], start: usize) -> bool {
+    let escaped = start > 1 && chars[start - 2] == '\\';
+    match start.checked_sub(1).map(|i| chars[i]) {
+        None => return false,
+        Some('(') | Some('|') if !escaped => return false,
+        _ => {}
+    }
+    let mut pos = start + 1;
+    let skip = |pred: fn(char) -> bool, pos: &mut usize| {
+        let begin = *pos;
+        while chars.get(*pos).map_or(false, |&c| pred(c)) {
+            *pos += 1;
+        }
+        *pos - begin
+    };
+    skip(char::is_whitespace, &mut pos);
+    if skip(|c| c.is_ascii_digit(), &mut pos) == 0 {
+        return false;
+    }
+    skip(char::is_whitespace, &mut pos);
+    if chars.get(pos) == Some(&',') {
+        pos += 1;
+        skip(char::is_whitespace, &mut pos);
+        skip(|c| c.is_ascii_digit(), &mut pos);
+        skip(char::is_whitespace, &mut pos);
+    }
+    chars.get(pos) == Some(&'}')
+}
+
+/// Returns the position after the character class starting at `start`, including nested
+/// classes.
+fn skip_class(chars: &[char], start: usize) -> usize {
+    let mut pos = start + 1;
+    if chars.get(pos) == Some(&'^') {
+        pos += 1;
+    }
+    if chars.get(pos) == Some(&']') {
+        pos += 1;
+    }
+    let mut depth = 1;
+    while depth > 0 && pos < chars.len() {
+        match chars[pos] {
+            '\\' => pos += 1,
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            _ => {}
+        }
+        pos += 1;
+    }
+    pos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: &[Construct] = &[
+        Construct::Lookahead,
+        Construct::NegativeLookahead,
+        Construct::Lookbehind,
+        Construct::NegativeLookbehind,
+        Construct::AtomicGroup,
+        Construct::Conditional,
+        Construct::AbsentOperator,
+        Construct::Backreference,
+        Construct::SubroutineCall,
+        Construct::PossessiveQuantifier,
+        Construct::NestedQuantifier,
+        Construct::KeepOut,
+        Construct::ContinueAnchor,
+        Construct::HexDigit,
+        Construct::BareBrace,
+    ];
+
+    #[test]
+    fn test_find_construct() {
+        let find = |regex| find_construct(regex, ALL);
+        assert_eq!(Some(Construct::Lookahead), find(r"a(?=b)"));
+        assert_eq!(Some(Construct::NegativeLookahead), find(r"a(?!b)"));
+        assert_eq!(Some(Construct::Lookbehind), find(r"(?<=a)b"));
+        assert_eq!(Some(Construct::NegativeLookbehind), find(r"(?<![0-9])b"));
+        assert_eq!(Some(Construct::AtomicGroup), find(r"(?>a|ab)c"));
+        assert_eq!(Some(Construct::Conditional), find(r"(a)?(?(1)b|c)"));
+        assert_eq!(Some(Construct::AbsentOperator), find(r"(?~abc)"));
+        assert_eq!(Some(Construct::Backreference), find(r"(a)\1"));
+        assert_eq!(Some(Construct::Backreference), find(r"(?<x>a)\k<x>"));
+        assert_eq!(Some(Construct::SubroutineCall), find(r"(?<x>a)\g<x>"));
+        assert_eq!(Some(Construct::PossessiveQuantifier), find(r"a++b"));
+        assert_eq!(Some(Construct::NestedQuantifier), find(r"LEN=\w+.?*TOS"));
+        assert_eq!(Some(Construct::KeepOut), find(r"a\Kb"));
+        assert_eq!(Some(Construct::ContinueAnchor), find(r"\Ga"));
+        assert_eq!(Some(Construct::HexDigit), find(r"\h{32}"));
+        assert_eq!(Some(Construct::HexDigit), find(r"a\H"));
+        assert_eq!(Some(Construct::BareBrace), find(r"Parameters: {(?<p>.*?)}"));
+        assert_eq!(Some(Construct::BareBrace), find(r"a{b"));
+        assert_eq!(Some(Construct::BareBrace), find(r"a{,3}"));
+        assert_eq!(Some(Construct::BareBrace), find(r"a|{2}"));
+    }
+
+    #[test]
+    fn test_find_construct_skips_classes_and_escapes() {
+        let find = |regex| find_construct(regex, ALL);
+        assert_eq!(None, find(r"(?<name>a+?)(?:b*)\d+"));
+        assert_eq!(None, find(r"[(?=][\]?+]\(?=\\\\"));
+        assert_eq!(None, find(r"\++\0"));
+        assert_eq!(None, find(r"a{2}b{2,}c{ 2, 5 }\({2}[{]\{"));
+        assert_eq!(
+            None,
+            find_construct(r"(?<=a)", &[Construct::AtomicGroup, Construct::Lookahead])
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use super::{Construct, Engine, EngineError, Flags, Locations};
+use ::onig::{RegexOptions, Region, SearchOptions, Syntax};
+
+/// The Oniguruma engine, which supports everything the default patterns use.
+#[derive(Debug)]
+pub(crate) struct Regex(::onig::Regex);
+
+impl Engine for Regex {
+    const NAME: &'static str = "onig";
+    const UNSUPPORTED: &'static [Construct] = &[];
+    const BACKTRACKING: bool = true;
+
+    fn compile(regex: &str, flags: Flags) -> Result<Self, EngineError> {
+        let mut options = RegexOptions::REGEX_OPTION_NONE;
+        if flags.case_insensitive {
+            options |= RegexOptions::REGEX_OPTION_IGNORECASE;
+        }
+        if flags.ignore_whitespace {
+            options |= RegexOptions::REGEX_OPTION_EXTEND;
+        }
+        // In the Ruby syntax, the multiline option lets `.` match a newline.
+        if flags.dot_matches_new_line {
+            options |= RegexOptions::REGEX_OPTION_MULTILINE;
+        }
+        ::onig::Regex::with_options(regex, options, Syntax::default())
+            .map(Regex)
+            .map_err(|e| EngineError::new(Self::NAME, e.description().into(), Some(e.code()), None))
+    }
+
+    fn captures_at(&self, text: &str, start: usize) -> Option<Locations> {
+        let mut region = Region::new();
+        self.0.search_with_options(
+            text,
+            start,
+            text.len(),
+            SearchOptions::SEARCH_OPTION_NONE,
+            Some(&mut region),
+        )?;
+        Some((0..region.len()).map(|i| region.pos(i)).collect())
+    }
+
+    fn capture_names(&self) -> Vec<(String, Vec<u32>)> {
+        let mut names = Vec::new();
+        self.0.foreach_name(|name, indices| {
+            names.push((name.to_string(), indices.to_vec()));
+            true
+        });
+        names
+    }
+
+    fn captures_len(&self) -> usize {
+        self.0.captures_len()
+    }
+}
@@ -0,0 +1,63 @@
+use super::{Construct, Engine, EngineError, Flags, Locations};
+use regex::RegexBuilder;
+
+/// The `regex` engine, which guarantees linear time matching but has no support for
+/// lookaround, atomic groups, backreferences, `\h` or literal braces.
+#[derive(Debug)]
+pub(crate) struct Regex(regex::Regex);
+
+impl Engine for Regex {
+    const NAME: &'static str = "regex";
+    const UNSUPPORTED: &'static [Construct] = &[
+        Construct::Lookahead,
+        Construct::NegativeLookahead,
+        Construct::Lookbehind,
+        Construct::NegativeLookbehind,
+        Construct::AtomicGroup,
+        Construct::Conditional,
+        Construct::AbsentOperator,
+        Construct::Backreference,
+        Construct::SubroutineCall,
+        Construct::PossessiveQuantifier,
+        Construct::NestedQuantifier,
+        Construct::KeepOut,
+        Construct::ContinueAnchor,
+        Construct::HexDigit,
+        Construct::BareBrace,
+    ];
+    const BACKTRACKING: bool = false;
+
+    fn compile(regex: &str, flags: Flags) -> Result<Self, EngineError> {
+        // Like Oniguruma with the Ruby syntax, `^` and `$` match at line boundaries.
+        RegexBuilder::new(regex)
+            .multi_line(true)
+            .case_insensitive(flags.case_insensitive)
+            .ignore_whitespace(flags.ignore_whitespace)
+            .dot_matches_new_line(flags.dot_matches_new_line)
+            .build()
+            .map(Regex)
+            .map_err(|e| EngineError::new(Self::NAME, e.to_string(), None, None))
+    }
+
+    fn captures_at(&self, text: &str, start: usize) -> Option<Locations> {
+        self.0
+            .captures_at(text, start)
+            .map(|c| c.iter().map(|m| m.map(|m| (m.start(), m.end()))).collect())
+    }
+
+    fn capture_names(&self) -> Vec<(String, Vec<u32>)> {
+        self.0
+            .capture_names()
+            .enumerate()
+            .filter_map(|(i, name)| name.map(|name| (name.to_string(), vec![i as u32])))
+            .collect()
+    }
+
+    fn captures_len(&self) -> usize {
+        // Unnamed groups always capture, but are not counted once there are named groups.
+        match self.0.capture_names().flatten().count() {
+            0 => self.0.captures_len() - 1,
+            named => named,
+        }
+    }
+}
@@ -8,49 +8,194 @@
 
 include!(concat!(env!("OUT_DIR"), "/default_patterns.rs"));
 
-use onig::{Captures, Regex};
+#[cfg(feature = "serde")]
+mod de;
+mod engine;
+mod lint;
+mod nested;
+mod options;
+mod parser;
+mod pattern_file;
+mod prefilter;
+#[cfg(feature = "serde")]
+mod ser;
+mod set;
+mod suggest;
+mod value;
+
+pub use crate::engine::EngineError;
+pub use crate::lint::{Finding, LintReport, Source};
+pub use crate::nested::Nested;
+pub use crate::options::CompileOptions;
+pub use crate::set::{PatternSet, SetMatch, SetMatchesIter};
+pub use crate::value::{Converter, CustomValue, Value};
+
+use crate::engine::{Engine, Flags, Locations, Regex};
+use crate::parser::{GroupName, Node};
+use crate::prefilter::Prefilter;
+use crate::value::{builtin_converters, Conversion};
 use std::collections::btree_map::Iter as MapIter;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::error::Error as StdError;
 use std::fmt;
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
 
 const MAX_RECURSION: usize = 1024;
 
-const GROK_PATTERN: &str = r"%\{(?<name>(?<pattern>[A-z0-9]+)(?::(?<alias>[A-z0-9_:;\/\s\.]+))?)(?:=(?<definition>(?:(?:[^{}]+|\.+)+)+))?\}";
-const NAME_INDEX: usize = 1;
-const PATTERN_INDEX: usize = 2;
-const ALIAS_INDEX: usize = 3;
-const DEFINITION_INDEX: usize = 4;
+/// The prefix of the names of all capture groups in a compiled pattern, followed by their
+/// number. Named groups written by the user are renamed as well, so no name can collide.
+const GROUP_PREFIX: &str = "grok";
 
 /// Returns the default patterns, also used by the default constructor of `Grok`.
+///
+/// Only the pattern files enabled through their `patterns-*` cargo feature are included,
+/// which are all of them with the `default-patterns` feature.
 pub fn patterns<'a>() -> &'a [(&'a str, &'a str)] {
     PATTERNS
 }
 
+/// The flavor of the default patterns, which decides how the captured fields are named.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum Compat {
+    /// The legacy flat field names, like `clientip` and `verb`.
+    Legacy,
+    /// The field names of the Elastic Common Schema (ECS) v1 as used by the `ecs-v1`
+    /// patterns of logstash, like `[source][address]` and `[http][request][method]`.
+    ///
+    /// Only the grok, httpd and linux-syslog packs have been ported so far, see
+    /// `Pack::supports`. The patterns of all other packs are not available in this flavor.
+    EcsV1,
+}
+
+/// Returns the default patterns of a single pattern file.
+///
+/// The result is empty if the pack is not enabled, see `Pack::is_enabled`.
+pub fn patterns_in<'a>(pack: Pack) -> &'a [(&'a str, &'a str)] {
+    &PATTERNS[pack.range()]
+}
+
 /// The `Matches` represent matched results from a `Pattern` against a provided text.
 #[derive(Debug)]
 pub struct Matches<'a> {
-    captures: Captures<'a>,
-    names: &'a BTreeMap<String, u32>,
+    text: &'a str,
+    locations: Locations,
+    names: &'a BTreeMap<String, Vec<u32>>,
+    types: &'a BTreeMap<String, Conversion>,
+    len: usize,
+    keep_empty: bool,
+    // Only read when serializing.
+    #[cfg_attr(not(feature = "serde"), allow(dead_code))]
+    collect_repeated: bool,
 }
 
 impl<'a> Matches<'a> {
-    /// Instantiates the matches for a pattern after the match.
-    fn new(captures: Captures<'a>, names: &'a BTreeMap<String, u32>) -> Self {
-        Matches { captures, names }
+    /// Instantiates the matches for a pattern after the match, where `len` is the number
+    /// of capture groups belonging to the pattern. Unless `keep_empty` is set, groups
+    /// which matched empty text are treated as if they did not participate. If
+    /// `collect_repeated` is set, fields captured more than once are serialized as arrays.
+    fn new(
+        text: &'a str,
+        locations: Locations,
+        names: &'a BTreeMap<String, Vec<u32>>,
+        types: &'a BTreeMap<String, Conversion>,
+        len: usize,
+        keep_empty: bool,
+        collect_repeated: bool,
+    ) -> Self {
+        Matches {
+            text,
+            locations,
+            names,
+            types,
+            len,
+            keep_empty,
+            collect_repeated,
+        }
     }
 
     /// Gets the value for the name (or) alias if found, `None` otherwise.
+    ///
+    /// If the name is captured more than once, like in `%{IPV4:addr}|%{HOSTNAME:addr}`,
+    /// the value of the group which participated in the match is returned. If several of
+    /// them participated, the last one wins.
     pub fn get(&self, name_or_alias: &str) -> Option<&str> {
         match self.names.get(name_or_alias) {
-            Some(found) => self.captures.at(*found as usize),
+            Some(found) => self.participating(found).map(|(_, value)| value),
             None => None,
         }
     }
 
+    /// Gets the values of all the groups captured for the name (or) alias which
+    /// participated in the match, in the order they appear in the pattern.
+    ///
+    /// This collects every value of a name which is captured more than once, like `WORD`
+    /// in `%{WORD} %{WORD} %{WORD}`, where `get` only returns the last one.
+    pub fn get_all(&self, name_or_alias: &str) -> Vec<&str> {
+        match self.names.get(name_or_alias) {
+            Some(found) => found.iter().filter_map(|idx| self.group(*idx)).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns the index and the text of the last of the groups which participated.
+    fn participating(&self, indices: &[u32]) -> Option<(u32, &'a str)> {
+        indices
+            .iter()
+            .rev()
+            .find_map(|idx| self.group(*idx).map(|value| (*idx, value)))
+    }
+
+    /// Returns the text of the capture group with the given index, if it participated.
+    fn group(&self, index: u32) -> Option<&'a str> {
+        self.group_span(index).map(|span| &self.text[span])
+    }
+
+    /// Returns the byte range of the capture group with the given index, if it
+    /// participated.
+    fn group_span(&self, index: u32) -> Option<Range<usize>> {
+        match self.locations.get(index as usize) {
+            Some(&Some((start, end))) if start < end || self.keep_empty => Some(start..end),
+            _ => None,
+        }
+    }
+
+    /// Gets the byte range of the value for the name (or) alias in the matched text if
+    /// found, `None` otherwise.
+    ///
+    /// The range belongs to the same group as the value returned by `get`, so
+    /// `&text[span]` is that value.
+    pub fn span(&self, name_or_alias: &str) -> Option<Range<usize>> {
+        let indices = self.names.get(name_or_alias)?;
+        self.participating(indices)
+            .and_then(|(idx, _)| self.group_span(idx))
+    }
+
+    /// Returns the byte range of the whole match in the matched text.
+    pub fn match_span(&self) -> Range<usize> {
+        match self.locations.first() {
+            Some(&Some((start, end))) => start..end,
+            _ => 0..0,
+        }
+    }
+
+    /// Gets the typed value for the name (or) alias if found, `None` otherwise.
+    ///
+    /// If the alias has been declared with a type suffix (like `%{INT:bytes:int}`), the
+    /// value is converted accordingly and a failed conversion is returned as an
+    /// `Error::ConversionFailed` for this field.
+    pub fn get_typed(&self, name_or_alias: &str) -> Option<Result<Value<'_>, Error>> {
+        self.get(name_or_alias)
+            .map(|value| typed_value(self.types, name_or_alias, value))
+    }
+
     /// Returns the number of matches.
     pub fn len(&self) -> usize {
-        self.captures.len() - 1
+        self.len
     }
 
     /// Returns true if there are no matches, false otherwise.
@@ -63,10 +208,53 @@ impl<'a> Matches<'a> {
     /// Note that if no match is found, the value is empty.
     pub fn iter(&'a self) -> MatchesIter<'a> {
         MatchesIter {
-            captures: &self.captures,
+            matches: self,
+            names: self.names.iter(),
+        }
+    }
+
+    /// Returns a tuple of key/value/byte range with all the matches found, where the
+    /// range is the one returned by `span`.
+    pub fn iter_spans(&'a self) -> SpansIter<'a> {
+        SpansIter {
+            matches: self,
             names: self.names.iter(),
         }
     }
+
+    /// Returns a tuple of key/typed value with all the matches found.
+    ///
+    /// Each value is converted individually, so a failed conversion of one field does
+    /// not prevent the others from being returned.
+    pub fn iter_typed(&'a self) -> TypedMatchesIter<'a> {
+        TypedMatchesIter {
+            inner: self.iter(),
+            types: self.types,
+        }
+    }
+
+    /// Returns the typed values of all matches as a tree, splitting field names like
+    /// `[http][response][status_code]` or `http.response.status_code` into their path.
+    ///
+    /// Fails with `Error::NestedFieldConflict` if two fields end up at the same path or if
+    /// a path is both a value and the parent of other fields, and with
+    /// `Error::ConversionFailed` if a value cannot be converted.
+    pub fn to_nested(&self) -> Result<Nested<'_>, Error> {
+        let fields = self
+            .iter()
+            .map(|(name, value)| typed_value(self.types, name, value).map(|v| (name, v)))
+            .collect::<Result<Vec<_>, _>>()?;
+        nested::build(fields)
+    }
+}
+
+/// Converts the value of the field if a type has been declared for it.
+fn typed_value<'a>(
+    types: &BTreeMap<String, Conversion>,
+    name: &str,
+    value: &'a str,
+) -> Result<Value<'a>, Error> {
+    Conversion::apply(types.get(name), name, value)
 }
 
 impl<'a> IntoIterator for &'a Matches<'a> {
@@ -80,8 +268,8 @@ impl<'a> IntoIterator for &'a Matches<'a> {
 
 /// An `Iterator` over all matches, accessible via `Matches`.
 pub struct MatchesIter<'a> {
-    captures: &'a Captures<'a>,
-    names: MapIter<'a, String, u32>,
+    matches: &'a Matches<'a>,
+    names: MapIter<'a, String, Vec<u32>>,
 }
 
 impl<'a> Iterator for MatchesIter<'a> {
@@ -90,8 +278,8 @@ impl<'a> Iterator for MatchesIter<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         //while let Some((k, v)) = self.names.next() {
         for (k, v) in self.names.by_ref() {
-            match self.captures.at(*v as usize) {
-                Some(value) => return Some((k.as_str(), value)),
+            match self.matches.participating(v) {
+                Some((_, value)) => return Some((k.as_str(), value)),
                 None => {
                     continue;
                 }
@@ -101,170 +289,800 @@ impl<'a> Iterator for MatchesIter<'a> {
     }
 }
 
+/// An `Iterator` over all matches with their byte ranges, accessible via `Matches`.
+pub struct SpansIter<'a> {
+    matches: &'a Matches<'a>,
+    names: MapIter<'a, String, Vec<u32>>,
+}
+
+impl<'a> Iterator for SpansIter<'a> {
+    type Item = (&'a str, &'a str, Range<usize>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for (k, v) in self.names.by_ref() {
+            if let Some((idx, value)) = self.matches.participating(v) {
+                let span = self.matches.group_span(idx)?;
+                return Some((k.as_str(), value, span));
+            }
+        }
+        None
+    }
+}
+
+/// An `Iterator` over all matches with their typed values, accessible via `Matches`.
+pub struct TypedMatchesIter<'a> {
+    inner: MatchesIter<'a>,
+    types: &'a BTreeMap<String, Conversion>,
+}
+
+impl<'a> Iterator for TypedMatchesIter<'a> {
+    type Item = (&'a str, Result<Value<'a>, Error>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next()
+            .map(|(name, value)| (name, typed_value(self.types, name, value)))
+    }
+}
+
 /// The `Pattern` represents a compiled regex, ready to be matched against arbitrary text.
 #[derive(Debug)]
 pub struct Pattern {
     regex: Regex,
-    names: BTreeMap<String, u32>,
+    names: BTreeMap<String, Vec<u32>>,
+    types: BTreeMap<String, Conversion>,
+    prefilter: Option<Prefilter>,
+    keep_empty: bool,
+    collect_repeated: bool,
 }
 
 impl Pattern {
     /// Creates a new pattern from a raw regex string and an alias map to identify the
-    /// fields properly.
-    fn new(regex: &str, alias: &HashMap<String, String>) -> Result<Self, Error> {
-        match Regex::new(regex) {
-            Ok(r) => Ok({
-                let mut names = BTreeMap::new();
-                r.foreach_name(|cap_name, cap_idx| {
-                    let name = match alias.iter().find(|&(_k, v)| *v == cap_name) {
-                        Some(item) => item.0.clone(),
-                        None => String::from(cap_name),
-                    };
-                    names.insert(name, cap_idx[0]);
-                    true
-                });
-                Pattern { regex: r, names }
-            }),
-            Err(_) => Err(Error::RegexCompilationFailed(regex.into())),
-        }
+    /// fields properly, together with the type conversions declared per field.
+    fn new(
+        regex: &str,
+        alias: &HashMap<String, String>,
+        types: BTreeMap<String, Conversion>,
+        flags: Flags,
+        keep_empty: bool,
+        collect_repeated: bool,
+    ) -> Result<Self, Error> {
+        let r = engine::compile(regex, flags)?;
+        // The literals of the prefilter are matched as-is, which is wrong if the case or
+        // the whitespace of the regex is ignored.
+        let prefilter = if flags.case_insensitive || flags.ignore_whitespace {
+            None
+        } else {
+            Prefilter::new(regex)
+        };
+        Ok(Pattern {
+            names: field_names(&r, alias),
+            regex: r,
+            types,
+            prefilter,
+            keep_empty,
+            collect_repeated,
+        })
     }
 
     /// Matches this compiled `Pattern` against the text and returns the matches.
     pub fn match_against<'a>(&'a self, text: &'a str) -> Option<Matches<'a>> {
+        if !self.may_match(text) {
+            return None;
+        }
+        self.match_unfiltered(text)
+    }
+
+    /// Matches the regex of this `Pattern` against the text, without checking the
+    /// prefilter first.
+    fn match_unfiltered<'a>(&'a self, text: &'a str) -> Option<Matches<'a>> {
         self.regex
             .captures(text)
-            .map(|cap| Matches::new(cap, &self.names))
+            .map(|locations| self.matches(text, locations))
+    }
+
+    /// Wraps the locations of the capture groups of this `Pattern` in the text as matches.
+    fn matches<'a>(&'a self, text: &'a str, locations: Locations) -> Matches<'a> {
+        Matches::new(
+            text,
+            locations,
+            &self.names,
+            &self.types,
+            self.regex.captures_len(),
+            self.keep_empty,
+            self.collect_repeated,
+        )
+    }
+
+    /// Returns an iterator over all non-overlapping matches in the text, in the order they
+    /// appear in the text.
+    ///
+    /// An empty match is only returned if it does not start where the previous match
+    /// ended, and the search continues one character after it.
+    pub fn captures_iter<'a>(&'a self, text: &'a str) -> CapturesIter<'a> {
+        CapturesIter {
+            pattern: self,
+            text,
+            // Past the end of the text if the prefilter rules out any match.
+            pos: if self.may_match(text) {
+                0
+            } else {
+                text.len() + 1
+            },
+            last_end: None,
+        }
+    }
+
+    /// Returns an iterator over the byte ranges of all non-overlapping matches in the
+    /// text, see `captures_iter`.
+    pub fn find_iter<'a>(&'a self, text: &'a str) -> FindIter<'a> {
+        FindIter {
+            inner: self.captures_iter(text),
+        }
+    }
+
+    /// Returns false if the prefilter rules out that this `Pattern` matches the text.
+    fn may_match(&self, text: &str) -> bool {
+        self.prefilter.as_ref().map_or(true, |p| p.may_match(text))
+    }
+
+    /// Returns true if this `Pattern` checks for the literals every match has to contain
+    /// before running the regex, which quickly rejects most of the non-matching text.
+    ///
+    /// The prefilter is only active if such literals could be found in the regex.
+    pub fn has_prefilter(&self) -> bool {
+        self.prefilter.is_some()
     }
 
     /// Returns all names this `Pattern` captures.
     pub fn capture_names(&self) -> impl Iterator<Item = &str> {
         self.names.keys().map(|s| s.as_str())
     }
+
+    /// Returns the name of the converter applied to the given capture, if it has one.
+    pub fn capture_conversion(&self, name: &str) -> Option<&str> {
+        self.types.get(name).map(|c| c.name())
+    }
+}
+
+/// An `Iterator` over all non-overlapping matches of a `Pattern` in a text, accessible via
+/// `Pattern::captures_iter`.
+pub struct CapturesIter<'a> {
+    pattern: &'a Pattern,
+    text: &'a str,
+    /// The byte offset to continue the search at.
+    pos: usize,
+    /// The end of the previous match, to skip an empty match right after it.
+    last_end: Option<usize>,
+}
+
+impl<'a> Iterator for CapturesIter<'a> {
+    type Item = Matches<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pos <= self.text.len() {
+            let locations = match self.pattern.regex.captures_at(self.text, self.pos) {
+                Some(locations) => locations,
+                None => {
+                    self.pos = self.text.len() + 1;
+                    return None;
+                }
+            };
+            let (start, end) = locations[0].expect("a match has a location");
+            if start == end {
+                // Continue after the next character, which ends the search at the end.
+                self.pos = end + self.text[end..].chars().next().map_or(1, |c| c.len_utf8());
+                if self.last_end == Some(end) {
+                    continue;
+                }
+            } else {
+                self.pos = end;
+            }
+            self.last_end = Some(end);
+
+            return Some(self.pattern.matches(self.text, locations));
+        }
+        None
+    }
+}
+
+/// An `Iterator` over the byte ranges of all non-overlapping matches of a `Pattern` in a
+/// text, accessible via `Pattern::find_iter`.
+pub struct FindIter<'a> {
+    inner: CapturesIter<'a>,
+}
+
+impl<'a> Iterator for FindIter<'a> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|matches| matches.match_span())
+    }
+}
+
+/// Maps the capture groups of the regex to the name of their field, as given by the alias
+/// map. Groups without a field are left out.
+///
+/// Several groups map to the same field if it is captured more than once, like in
+/// `%{IPV4:addr}|%{HOSTNAME:addr}`, so every field has the indices of all its groups in
+/// ascending order.
+fn field_names(regex: &Regex, alias: &HashMap<String, String>) -> BTreeMap<String, Vec<u32>> {
+    let mut names: BTreeMap<String, Vec<u32>> = BTreeMap::new();
+    for (cap_name, cap_idx) in &regex.capture_names() {
+        let name = match alias.get(cap_name) {
+            Some(name) => name,
+            None => continue,
+        };
+        names
+            .entry(name.clone())
+            .or_default()
+            .extend_from_slice(cap_idx);
+    }
+    for indices in names.values_mut() {
+        indices.sort_unstable();
+    }
+    names
+}
+
+/// Checks that a field name is either a plain name or a field reference of one or more
+/// bracketed parts like `[source][address]`.
+fn is_valid_field_name(name: &str) -> bool {
+    match name.strip_prefix('[') {
+        Some(rest) => match rest.strip_suffix(']') {
+            Some(parts) => parts
+                .split("][")
+                .all(|part| !part.is_empty() && !part.contains(&['[', ']'][..])),
+            None => false,
+        },
+        None => !name.contains(&['[', ']'][..]),
+    }
+}
+
+/// A grok pattern expanded into a regex, before it is handed to the regex engine.
+struct Expansion {
+    /// The expanded regex.
+    regex: String,
+    /// Maps the name of each capture group to the name of its field.
+    alias: HashMap<String, String>,
+    /// Maps the name of each named group written by the user to the name it has been
+    /// renamed to, the last one if a name is used more than once.
+    renamed: HashMap<String, String>,
+    /// The conversions declared per field.
+    types: BTreeMap<String, Conversion>,
+    /// The index the next capture group name would have used.
+    next_index: usize,
+    /// The references to patterns which are not defined.
+    missing: Vec<MissingDefinition>,
+    /// The part of the regex each reference has been expanded into, in the order the
+    /// references have been expanded.
+    sections: Vec<Section>,
+    /// The flags to compile the regex with.
+    flags: Flags,
+    /// Whether fields which matched empty text are kept.
+    keep_empty: bool,
+    /// Whether fields captured more than once are serialized as arrays.
+    collect_repeated: bool,
+}
+
+/// The part of an expanded regex which a single reference has been expanded into.
+struct Section {
+    /// The byte range in the regex, including the group around the definition.
+    range: Range<usize>,
+    /// The reference as written in the pattern.
+    reference: String,
+    /// The name of the pattern whose definition holds the reference, if any.
+    pattern: Option<String>,
+}
+
+impl Expansion {
+    /// Returns the name for the next capture group.
+    fn next_group(&mut self) -> String {
+        let group = format!("{}{}", GROUP_PREFIX, self.next_index);
+        self.next_index += 1;
+        group
+    }
+
+    /// Appends regex text, renaming the named groups in it and the backreferences to them.
+    ///
+    /// A named group becomes a field with the name of the group, unless the field is not
+    /// allowed by the options. It is captured even in alias-only mode, because its name
+    /// is given explicitly just like an alias.
+    fn push_regex(&mut self, regex: &str, options: &CompileOptions) {
+        let mut last = 0;
+        for (range, name) in parser::group_names(regex) {
+            self.regex.push_str(&regex[last..range.start]);
+            last = range.end;
+            match name {
+                GroupName::Group(field) => {
+                    let group = self.next_group();
+                    self.regex.push_str(&group);
+                    if options.captures(field) {
+                        self.alias.insert(group.clone(), field.into());
+                    }
+                    self.renamed.insert(field.into(), group);
+                }
+                GroupName::Backreference(field) => match self.renamed.get(field) {
+                    Some(group) => self.regex.push_str(group),
+                    None => self.regex.push_str(field),
+                },
+            }
+        }
+        self.regex.push_str(&regex[last..]);
+    }
+
+    /// Compiles the expanded regex into a `Pattern`.
+    ///
+    /// If the regex engine fails, the error names the innermost reference whose part of
+    /// the regex fails to compile on its own, which is the one the problem comes from.
+    fn compile(&self) -> Result<Pattern, Error> {
+        let pattern = Pattern::new(
+            &self.regex,
+            &self.alias,
+            self.types.clone(),
+            self.flags,
+            self.keep_empty,
+            self.collect_repeated,
+        );
+        pattern.map_err(|error| match error {
+            Error::RegexCompilationFailed {
+                regex,
+                reference: None,
+                pattern: None,
+                source,
+            } => {
+                let mut sections = self.sections.iter().collect::<Vec<_>>();
+                sections.sort_by_key(|s| s.range.len());
+                let section = sections
+                    .into_iter()
+                    .find(|s| Regex::compile(&self.regex[s.range.clone()], self.flags).is_err());
+                Error::RegexCompilationFailed {
+                    regex,
+                    reference: section.map(|s| s.reference.clone()),
+                    pattern: section.and_then(|s| s.pattern.clone()),
+                    source,
+                }
+            }
+            error => error,
+        })
+    }
 }
 
 /// The `Grok` struct is the main entry point into using this library.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Grok {
     patterns: BTreeMap<String, String>,
+    sources: BTreeMap<String, Vec<Source>>,
+    converters: BTreeMap<String, Arc<dyn Converter>>,
 }
 
 impl Grok {
     /// Creates a new `Grok` instance with no patterns.
+    ///
+    /// The built-in `int`, `float` and `bool` converters are always available.
     pub fn empty() -> Self {
         Grok {
             patterns: BTreeMap::new(),
+            sources: BTreeMap::new(),
+            converters: builtin_converters()
+                .into_iter()
+                .map(|(name, converter)| (String::from(name), converter))
+                .collect(),
         }
     }
 
-    /// Creates a new `Grok` instance and loads all the default patterns.
+    /// Creates a new `Grok` instance and loads all the default patterns, see `patterns()`.
     pub fn with_default_patterns() -> Self {
+        Grok::with_pattern_packs(Pack::all())
+    }
+
+    /// Creates a new `Grok` instance and loads all the default patterns in the given flavor.
+    ///
+    /// With `Compat::EcsV1`, only the packs for which `Pack::supports(Compat::EcsV1)` is
+    /// true are loaded, so legacy and ECS field names are never mixed. Their patterns which
+    /// capture fields are replaced by the ECS variant, so `%{HTTPD_COMMONLOG}` captures
+    /// `[source][address]` instead of `clientip`.
+    pub fn with_default_patterns_compat(compat: Compat) -> Self {
+        let packs = Pack::all()
+            .iter()
+            .copied()
+            .filter(|pack| pack.supports(compat))
+            .collect::<Vec<_>>();
+        let mut grok = Grok::with_pattern_packs(&packs);
+        if compat == Compat::EcsV1 {
+            for &(key, value) in ECS_V1_PATTERNS {
+                // The ECS variant is meant to replace the pattern, so it does not count
+                // as a duplicate definition.
+                grok.sources.remove(key);
+                grok.insert_pattern(key.into(), value.into(), Source::Compat(compat));
+            }
+        }
+        grok
+    }
+
+    /// Creates a new `Grok` instance which only loads the default patterns of the given
+    /// packs, in order, so a pack overrides patterns of the same name in the ones before.
+    ///
+    /// This keeps patterns of unrelated files from changing the meaning of a pattern, for
+    /// example `Grok::with_pattern_packs(&[Pack::Grok, Pack::Httpd])` only knows the base
+    /// and the httpd patterns.
+    pub fn with_pattern_packs(packs: &[Pack]) -> Self {
         let mut grok = Grok::empty();
-        for &(key, value) in PATTERNS {
-            grok.add_pattern(String::from(key), String::from(value));
+        for &pack in packs {
+            for &(key, value) in patterns_in(pack) {
+                grok.insert_pattern(key.into(), value.into(), Source::Pack(pack));
+            }
         }
         grok
     }
 
     /// Adds a custom pattern.
     pub fn add_pattern<S: Into<String>>(&mut self, name: S, pattern: S) {
-        self.patterns.insert(name.into(), pattern.into());
+        self.insert_pattern(name.into(), pattern.into(), Source::Custom);
+    }
+
+    /// Adds the pattern and records where its definition comes from, unless the same
+    /// source defines it again without changing it.
+    fn insert_pattern(&mut self, name: String, pattern: String, source: Source) {
+        let sources = self.sources.entry(name.clone()).or_default();
+        if sources.last() == Some(&source) && self.patterns.get(&name) == Some(&pattern) {
+            return;
+        }
+        sources.push(source);
+        self.patterns.insert(name, pattern);
+    }
+
+    /// Adds all patterns read from the reader, which uses the same format as the pattern
+    /// files shipped with this library: every line holds the name of a pattern and its
+    /// definition separated by the first space, empty lines and lines starting with `#`
+    /// are ignored.
+    ///
+    /// If a line is malformed, none of the patterns are added and the error holds its
+    /// line number.
+    pub fn add_patterns_from_reader<R: Read>(&mut self, reader: R) -> Result<(), Error> {
+        for (name, pattern) in read_patterns(reader, None)? {
+            self.insert_pattern(name, pattern, Source::Reader);
+        }
+        Ok(())
+    }
+
+    /// Adds all patterns of the given pattern file, see `add_patterns_from_reader` for the
+    /// format.
+    pub fn add_patterns_from_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let file = fs::File::open(path).map_err(|e| Error::PatternFileReadFailed {
+            file: Some(path.display().to_string()),
+            reason: e.to_string(),
+        })?;
+        for (name, pattern) in read_patterns(file, Some(path))? {
+            self.insert_pattern(name, pattern, Source::File(path.into()));
+        }
+        Ok(())
+    }
+
+    /// Adds the patterns of all files with the `.pattern` extension in the given directory,
+    /// in the order of their file names. Subdirectories are not searched.
+    ///
+    /// If one of the files cannot be read or is malformed, none of the patterns are added.
+    pub fn add_patterns_from_dir<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let read_failed = |e: std::io::Error| Error::PatternFileReadFailed {
+            file: Some(path.display().to_string()),
+            reason: e.to_string(),
+        };
+        let mut files = Vec::new();
+        for entry in fs::read_dir(path).map_err(read_failed)? {
+            let file = entry.map_err(read_failed)?.path();
+            if file.is_file() && file.extension().map_or(false, |e| e == "pattern") {
+                files.push(file);
+            }
+        }
+        files.sort();
+
+        let mut patterns = Vec::new();
+        for file in files {
+            let reader = fs::File::open(&file).map_err(|e| Error::PatternFileReadFailed {
+                file: Some(file.display().to_string()),
+                reason: e.to_string(),
+            })?;
+            patterns.push((read_patterns(reader, Some(&file))?, file));
+        }
+        for (file_patterns, file) in patterns {
+            for (name, pattern) in file_patterns {
+                self.insert_pattern(name, pattern, Source::File(file.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a custom converter which can be referenced by name as the type suffix of an
+    /// alias, like `%{IP:client:ip}`.
+    ///
+    /// Registering a converter with the name of an existing one replaces it.
+    pub fn add_converter<S: Into<String>, C: Converter + 'static>(
+        &mut self,
+        name: S,
+        converter: C,
+    ) {
+        self.converters.insert(name.into(), Arc::new(converter));
     }
 
     /// Compiles the given pattern, making it ready for matching.
+    ///
+    /// If `with_alias_only` is set, only references with an alias are captured. See
+    /// `compile_with` for more options.
     pub fn compile(&mut self, pattern: &str, with_alias_only: bool) -> Result<Pattern, Error> {
-        let mut named_regex = String::from(pattern);
-        let mut alias: HashMap<String, String> = HashMap::new();
+        self.compile_with(pattern, &CompileOptions::new().alias_only(with_alias_only))
+    }
 
-        let mut index = 0;
-        let mut iteration_left = MAX_RECURSION;
-        let mut continue_iteration = true;
+    /// Compiles the given pattern with the given options, making it ready for matching.
+    pub fn compile_with(
+        &mut self,
+        pattern: &str,
+        options: &CompileOptions,
+    ) -> Result<Pattern, Error> {
+        self.expand(pattern, options, 0)?.compile()
+    }
 
-        let grok_regex = match Regex::new(GROK_PATTERN) {
-            Ok(r) => r,
-            Err(_) => return Err(Error::RegexCompilationFailed(GROK_PATTERN.into())),
+    /// Expands all the references in the pattern into a regex, numbering the capture groups
+    /// it introduces starting at `first_index`.
+    fn expand(
+        &mut self,
+        pattern: &str,
+        options: &CompileOptions,
+        first_index: usize,
+    ) -> Result<Expansion, Error> {
+        let mut expansion = Expansion {
+            regex: String::new(),
+            alias: HashMap::new(),
+            renamed: HashMap::new(),
+            types: BTreeMap::new(),
+            next_index: first_index,
+            missing: Vec::new(),
+            sections: Vec::new(),
+            flags: options.flags,
+            keep_empty: options.keep_empty_captures,
+            collect_repeated: options.collect_repeated,
         };
+        if options.anchored {
+            expansion.regex.push_str(r"\A(?:");
+        }
+        let start = expansion.regex.len();
+        self.expand_into(&mut expansion, pattern, &mut Vec::new(), options)?;
+        let is_empty = expansion.regex.len() == start;
+        if options.anchored {
+            expansion.regex.push_str(r")\z");
+        }
+
+        if !expansion.missing.is_empty() {
+            Err(Error::DefinitionsNotFound(expansion.missing))
+        } else if is_empty {
+            Err(Error::CompiledPatternIsEmpty(pattern.into()))
+        } else {
+            Ok(expansion)
+        }
+    }
 
-        while continue_iteration {
-            continue_iteration = false;
-            if iteration_left == 0 {
-                return Err(Error::RecursionTooDeep);
+    /// Appends the regex of the expression to the expansion, resolving every reference
+    /// against the patterns. `chain` holds the names of the patterns which are being
+    /// expanded, the last one being the pattern the expression is the definition of.
+    fn expand_into(
+        &mut self,
+        expansion: &mut Expansion,
+        expression: &str,
+        chain: &mut Vec<String>,
+        options: &CompileOptions,
+    ) -> Result<(), Error> {
+        if chain.len() > MAX_RECURSION {
+            return Err(Error::RecursionTooDeep);
+        }
+
+        for node in parser::parse(expression) {
+            let reference = match node {
+                Node::Regex(regex) => {
+                    expansion.push_regex(regex, options);
+                    continue;
+                }
+                Node::Reference(reference) => reference,
+            };
+            let invalid = |error: Error| Error::InvalidReference {
+                reference: reference.text.into(),
+                pattern: chain.last().cloned(),
+                offset: reference.offset,
+                source: Box::new(error),
+            };
+
+            if let Some(definition) = reference.definition {
+                self.insert_pattern(reference.name.into(), definition.into(), Source::Inline);
             }
-            iteration_left -= 1;
-
-            if let Some(m) = grok_regex.captures(&named_regex.clone()) {
-                continue_iteration = true;
-                let raw_pattern = match m.at(PATTERN_INDEX) {
-                    Some(p) => p,
-                    None => {
-                        return Err(Error::GenericCompilationFailure(
-                            "Could not find pattern in matches".into(),
-                        ))
-                    }
-                };
-
-                let mut name = match m.at(NAME_INDEX) {
-                    Some(n) => String::from(n),
-                    None => {
-                        return Err(Error::GenericCompilationFailure(
-                            "Could not find name in matches".into(),
-                        ))
+            if let Some(start) = chain.iter().position(|name| name == reference.name) {
+                let mut cycle = chain[start..].to_vec();
+                cycle.push(reference.name.into());
+                return Err(Error::ReferenceCycle(cycle));
+            }
+            // A missing definition does not stop the expansion, so all of them are reported
+            // at once. The same reference is only reported once, even if the pattern holding
+            // it is used several times.
+            let definition = match self.patterns.get(reference.name) {
+                Some(d) => d.clone(),
+                None => {
+                    let missing = MissingDefinition {
+                        name: reference.name.into(),
+                        reference: reference.text.into(),
+                        pattern: chain.last().cloned(),
+                        offset: reference.offset,
+                        suggestions: suggest::similar_names(reference.name, self.patterns.keys()),
+                    };
+                    if !expansion.missing.contains(&missing) {
+                        expansion.missing.push(missing);
                     }
-                };
-
-                if let Some(definition) = m.at(DEFINITION_INDEX) {
-                    self.add_pattern(raw_pattern, definition);
-                    name = format!("{}={}", name, definition);
+                    continue;
                 }
-
-                // Since a pattern with a given name can show up more than once, we need to
-                // loop through the number of matches found and apply the transformations
-                // on each of them.
-                for _ in 0..named_regex.matches(&format!("%{{{}}}", name)).count() {
-                    // Check if we have a definition for the raw pattern key and fail quickly
-                    // if not.
-                    let pattern_definition = match self.patterns.get(raw_pattern) {
-                        Some(d) => d,
-                        None => return Err(Error::DefinitionNotFound(raw_pattern.into())),
+            };
+
+            let start = expansion.regex.len();
+            // If no alias is specified and all but with alias are ignored, or the field is
+            // not allowed, the definition is put into a group the regex engine does not
+            // capture. Otherwise it uses a named group, whose name is mapped to the alias or
+            // the name of the pattern.
+            let field = reference.alias.unwrap_or(reference.name);
+            if (options.alias_only && reference.alias.is_none()) || !options.captures(field) {
+                expansion.regex.push_str("(?:");
+            } else {
+                if let Some(conversion) = reference.conversion.filter(|_| options.convert_types) {
+                    let converter = match self.converters.get(conversion) {
+                        Some(c) => c.clone(),
+                        None => return Err(invalid(Error::ConverterNotFound(conversion.into()))),
                     };
+                    expansion
+                        .types
+                        .insert(field.into(), Conversion::new(conversion.into(), converter));
+                }
+                if !is_valid_field_name(field) {
+                    return Err(invalid(Error::InvalidFieldName(field.into())));
+                }
+                let group = expansion.next_group();
+                expansion.regex.push_str(&format!("(?<{}>", group));
+                expansion.alias.insert(group, field.into());
+            }
+            chain.push(reference.name.into());
+            self.expand_into(expansion, &definition, chain, options)?;
+            chain.pop();
+            expansion.regex.push(')');
+            expansion.sections.push(Section {
+                range: start..expansion.regex.len(),
+                reference: reference.text.into(),
+                pattern: chain.last().cloned(),
+            });
+        }
+        Ok(())
+    }
 
-                    // If no alias is specified and all but with alias are ignored,
-                    // the replacement tells the regex engine to ignore the matches.
-                    // Otherwise, the definition is turned into a regex that the
-                    // engine understands and uses a named group.
-
-                    let replacement = if with_alias_only && m.at(ALIAS_INDEX).is_none() {
-                        format!("(?:{})", pattern_definition)
-                    } else {
-                        // If an alias is specified by the user use that one to
-                        // match the name<index> conversion, otherwise just use
-                        // the name of the pattern definition directly.
-                        alias.insert(
-                            match m.at(ALIAS_INDEX) {
-                                Some(a) => a.into(),
-                                None => name.clone(),
-                            },
-                            format!("name{}", index),
-                        );
-
-                        format!("(?<name{}>{})", index, pattern_definition)
-                    };
+    /// Checks all the patterns for references which lead back to the pattern itself, like
+    /// `A` referring to `B` which refers to `A` again.
+    ///
+    /// Such patterns can never be compiled, so this allows to validate a set of patterns
+    /// up front. The first cycle found is returned as `Error::ReferenceCycle`, references
+    /// to undefined patterns are ignored.
+    pub fn check_cycles(&self) -> Result<(), Error> {
+        let mut checked = BTreeSet::new();
+        for name in self.patterns.keys() {
+            self.find_cycle(name, &mut Vec::new(), &mut checked)?;
+        }
+        Ok(())
+    }
 
-                    // Finally, look for the original %{...} style pattern and
-                    // replace it with our replacement (only the first occurrence
-                    // since we are iterating one by one).
-                    named_regex = named_regex.replacen(&format!("%{{{}}}", name), &replacement, 1);
+    /// Follows the references of the pattern depth first, where `chain` holds the patterns
+    /// leading to it and `checked` the ones which are known to be free of cycles.
+    fn find_cycle<'a>(
+        &'a self,
+        name: &'a str,
+        chain: &mut Vec<&'a str>,
+        checked: &mut BTreeSet<&'a str>,
+    ) -> Result<(), Error> {
+        if checked.contains(name) {
+            return Ok(());
+        }
+        if let Some(start) = chain.iter().position(|n| *n == name) {
+            let mut cycle = chain[start..]
+                .iter()
+                .map(|n| n.to_string())
+                .collect::<Vec<_>>();
+            cycle.push(name.into());
+            return Err(Error::ReferenceCycle(cycle));
+        }
+        let definition = match self.patterns.get(name) {
+            Some(d) => d,
+            None => return Ok(()),
+        };
 
-                    index += 1;
+        chain.push(name);
+        for node in parser::parse(definition) {
+            // An inline definition replaces the pattern when compiling, and cannot hold
+            // references itself.
+            if let Node::Reference(r) = node {
+                if r.definition.is_none() {
+                    self.find_cycle(r.name, chain, checked)?;
                 }
             }
         }
+        chain.pop();
+        checked.insert(name);
+        Ok(())
+    }
 
-        if named_regex.is_empty() {
-            Err(Error::CompiledPatternIsEmpty(pattern.into()))
-        } else {
-            Pattern::new(&named_regex, &alias)
+    /// Checks all the patterns and reports references to undefined patterns, patterns
+    /// which have been defined more than once, reference cycles, patterns no other pattern
+    /// uses and patterns which fail to compile.
+    ///
+    /// Unlike `compile`, this does not stop at the first problem, so a whole pattern
+    /// library can be validated at once.
+    pub fn lint(&self) -> LintReport {
+        lint::lint(self)
+    }
+
+    /// Compiles all the given patterns into a `PatternSet`, which tries them in order.
+    ///
+    /// If one of the patterns fails to compile, the error identifies its position.
+    pub fn compile_set<I, S>(
+        &mut self,
+        patterns: I,
+        with_alias_only: bool,
+    ) -> Result<PatternSet, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        PatternSet::new(self, patterns, with_alias_only, false)
+    }
+
+    /// Compiles all the given patterns into a `PatternSet` which additionally merges them
+    /// into a single regex, so `PatternSet::match_first` scans the text only once.
+    ///
+    /// The first pattern in order that matches wins, just like with `compile_set`. Only
+    /// patterns starting with `^` are merged, and each of them is matched at the start of
+    /// the text only. The prefilters of the patterns are checked first, the first pattern
+    /// they do not rule out is tried on its own and the ones after it are merged. The
+    /// merged regexes of such subsets are compiled on first use and kept, up to a limit
+    /// after which the remaining patterns are tried one after another.
+    ///
+    /// The `regex` engine cannot prefer the first pattern within a merged regex, so with
+    /// it the patterns are always tried one after another.
+    pub fn compile_set_single_pass<I, S>(
+        &mut self,
+        patterns: I,
+        with_alias_only: bool,
+    ) -> Result<PatternSet, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        PatternSet::new(self, patterns, with_alias_only, true)
+    }
+}
+
+/// Reads all patterns from a reader in the format of the pattern files, where `file` is
+/// the path of the file it reads from, if any.
+fn read_patterns<R: Read>(reader: R, file: Option<&Path>) -> Result<Vec<(String, String)>, Error> {
+    let file_name = || file.map(|f| f.display().to_string());
+    let mut patterns = Vec::new();
+    for (index, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line.map_err(|e| Error::PatternFileReadFailed {
+            file: file_name(),
+            reason: e.to_string(),
+        })?;
+        match pattern_file::parse_line(&line) {
+            Ok(Some((name, definition))) => patterns.push((name.into(), definition.into())),
+            Ok(None) => {}
+            Err(reason) => {
+                return Err(Error::PatternFileMalformed {
+                    file: file_name(),
+                    line: index + 1,
+                    reason,
+                })
+            }
         }
     }
+    Ok(patterns)
 }
 
 /// The Default implementation for Grok whuich will load the default patterns.
@@ -311,12 +1129,131 @@ pub enum Error {
     RecursionTooDeep,
     /// After compiling, the resulting compiled regex pattern is empty.
     CompiledPatternIsEmpty(String),
-    /// A corresponding pattern definition could not be found for the given name.
-    DefinitionNotFound(String),
     /// If the compilation for a specific regex in the underlying engine failed.
-    RegexCompilationFailed(String),
+    RegexCompilationFailed {
+        /// The regex which failed to compile.
+        regex: String,
+        /// The innermost reference whose part of the regex fails to compile, if it could
+        /// be found.
+        reference: Option<String>,
+        /// The name of the pattern whose definition holds the reference, or `None` if it
+        /// is part of the compiled expression itself.
+        pattern: Option<String>,
+        /// The error reported by the regex engine.
+        source: Box<EngineError>,
+    },
     /// Something is messed up during the compilation phase.
     GenericCompilationFailure(String),
+    /// The matched value of a field could not be converted into its declared type.
+    ConversionFailed {
+        /// The name of the field.
+        field: String,
+        /// The matched value which failed to convert.
+        value: String,
+        /// The name of the converter which has been applied.
+        conversion: String,
+        /// The reason reported by the converter.
+        reason: String,
+    },
+    /// A pattern of a `PatternSet` failed to compile.
+    PatternSetCompilationFailed {
+        /// The position of the pattern in the set.
+        index: usize,
+        /// The error the pattern failed to compile with.
+        source: Box<Error>,
+    },
+    /// A type suffix refers to a converter which has not been registered.
+    ConverterNotFound(String),
+    /// The matches could not be deserialized into the requested type.
+    DeserializationFailed {
+        /// The name of the field which failed to deserialize, if known.
+        field: Option<String>,
+        /// The reason reported by the deserializer.
+        message: String,
+    },
+    /// A pattern file could not be read.
+    PatternFileReadFailed {
+        /// The path of the file, if the patterns have been read from one.
+        file: Option<String>,
+        /// The reason reported by the operating system.
+        reason: String,
+    },
+    /// A line of a pattern file is malformed.
+    PatternFileMalformed {
+        /// The path of the file, if the patterns have been read from one.
+        file: Option<String>,
+        /// The number of the line, starting at 1.
+        line: usize,
+        /// What is wrong with the line.
+        reason: String,
+    },
+    /// A field name is neither a plain name nor a field reference like `[source][address]`.
+    InvalidFieldName(String),
+    /// The regex uses a construct which the selected regex engine does not support.
+    UnsupportedRegexConstruct {
+        /// The name of the regex engine, like `regex`.
+        engine: String,
+        /// A description of the unsupported construct, like `lookbehind`.
+        construct: String,
+        /// The regex which failed to compile.
+        regex: String,
+    },
+    /// Two fields cannot be nested because they use the same path, or the path of one is
+    /// a parent of the other.
+    NestedFieldConflict {
+        /// The name of the field which could not be added.
+        field: String,
+        /// The name of the field it conflicts with.
+        other: String,
+    },
+    /// A reference like `%{IP:client}` could not be resolved.
+    InvalidReference {
+        /// The reference as written in the pattern.
+        reference: String,
+        /// The name of the pattern whose definition holds the reference, or `None` if it
+        /// is part of the compiled expression itself.
+        pattern: Option<String>,
+        /// The byte offset of the reference in the expression or definition.
+        offset: usize,
+        /// Why the reference could not be resolved.
+        source: Box<Error>,
+    },
+    /// The patterns refer to each other in a cycle, which starts and ends with the same
+    /// pattern like `["A", "B", "A"]`.
+    ReferenceCycle(Vec<String>),
+    /// The definitions of one or more referenced patterns could not be found, in the order
+    /// of their references.
+    DefinitionsNotFound(Vec<MissingDefinition>),
+}
+
+/// A reference to a pattern which is not defined, see `Error::DefinitionsNotFound`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct MissingDefinition {
+    /// The name of the pattern which is not defined.
+    pub name: String,
+    /// The reference as written in the pattern, like `%{IPV4ADDR:ip}`.
+    pub reference: String,
+    /// The name of the pattern whose definition holds the reference, or `None` if it is
+    /// part of the compiled expression itself.
+    pub pattern: Option<String>,
+    /// The byte offset of the reference in the expression or definition.
+    pub offset: usize,
+    /// The names of defined patterns which are similar to the name, closest first.
+    pub suggestions: Vec<String>,
+}
+
+impl fmt::Display for MissingDefinition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\"{}\" at byte {}", self.reference, self.offset)?;
+        if let Some(ref pattern) = self.pattern {
+            write!(f, " of the pattern \"{}\"", pattern)?;
+        }
+        if !self.suggestions.is_empty() {
+            write!(f, " (did you mean {}?)", self.suggestions.join(", "))?;
+        }
+        Ok(())
+    }
 }
 
 impl StdError for Error {
@@ -324,16 +1261,34 @@ impl StdError for Error {
         match *self {
             Error::RecursionTooDeep => "compilation recursion reached the limit",
             Error::CompiledPatternIsEmpty(_) => "compiled pattern is empty",
-            Error::DefinitionNotFound(_) => "pattern definition not found while compiling",
-            Error::RegexCompilationFailed(_) => "regex compilation in the engine failed",
+            Error::RegexCompilationFailed { .. } => "regex compilation in the engine failed",
             Error::GenericCompilationFailure(_) => {
                 "something happened during the compilation phase"
             }
+            Error::ConversionFailed { .. } => "type conversion of a matched value failed",
+            Error::PatternSetCompilationFailed { .. } => "compilation of a pattern in a set failed",
+            Error::ConverterNotFound(_) => "converter not found while compiling",
+            Error::DeserializationFailed { .. } => "deserialization of the matches failed",
+            Error::PatternFileReadFailed { .. } => "reading a pattern file failed",
+            Error::PatternFileMalformed { .. } => "pattern file is malformed",
+            Error::InvalidFieldName(_) => "invalid field name",
+            Error::UnsupportedRegexConstruct { .. } => {
+                "regex construct not supported by the regex engine"
+            }
+            Error::NestedFieldConflict { .. } => "fields conflict when nested",
+            Error::InvalidReference { .. } => "reference to a pattern could not be resolved",
+            Error::ReferenceCycle(_) => "patterns refer to each other in a cycle",
+            Error::DefinitionsNotFound(_) => "pattern definitions not found while compiling",
         }
     }
 
-    fn cause(&self) -> Option<&dyn StdError> {
-        None
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match *self {
+            Error::RegexCompilationFailed { ref source, .. } => Some(&**source),
+            Error::PatternSetCompilationFailed { ref source, .. } => Some(&**source),
+            Error::InvalidReference { ref source, .. } => Some(&**source),
+            _ => None,
+        }
     }
 }
 
@@ -350,42 +1305,178 @@ impl fmt::Display for Error {
                 "The given pattern \"{}\" ended up compiling into an empty regex",
                 p
             ),
-            Error::DefinitionNotFound(ref d) => write!(
+            Error::RegexCompilationFailed {
+                reference: Some(ref reference),
+                pattern: Some(ref pattern),
+                ref source,
+                ..
+            } => write!(
                 f,
-                "The given pattern definition name \"{}\" could not be found in the definition map",
-                d
+                "The regex failed compilation in the underlying engine inside \"{}\" from \"{}\": {}",
+                reference, pattern, source
+            ),
+            Error::RegexCompilationFailed {
+                reference: Some(ref reference),
+                pattern: None,
+                ref source,
+                ..
+            } => write!(
+                f,
+                "The regex failed compilation in the underlying engine inside \"{}\": {}",
+                reference, source
             ),
-            Error::RegexCompilationFailed(ref r) => write!(
+            Error::RegexCompilationFailed {
+                ref regex,
+                reference: None,
+                ref source,
+                ..
+            } => write!(
                 f,
-                "The given regex \"{}\" failed compilation in the underlying engine",
-                r
+                "The given regex \"{}\" failed compilation in the underlying engine: {}",
+                regex, source
             ),
             Error::GenericCompilationFailure(ref d) => write!(
                 f,
                 "Something unexpected happened during the compilation phase: \"{}\"",
                 d
             ),
-        }
-    }
-}
-
-#[cfg(test)]
-mod tests {
-
-    use super::*;
-
-    #[test]
-    fn test_simple_anonymous_pattern() {
-        let mut grok = Grok::empty();
-        grok.add_pattern("USERNAME", r"[a-zA-Z0-9._-]+");
-        let pattern = grok
-            .compile("%{USERNAME}", false)
-            .expect("Error while compiling!");
-
-        let matches = pattern.match_against("root").expect("No matches found!");
-        assert_eq!("root", matches.get("USERNAME").unwrap());
-        assert_eq!(1, matches.len());
-        let matches = pattern
+            Error::ConversionFailed {
+                ref field,
+                ref value,
+                ref conversion,
+                ref reason,
+            } => write!(
+                f,
+                "The value \"{}\" of field \"{}\" could not be converted to \"{}\": {}",
+                value, field, conversion, reason
+            ),
+            Error::PatternSetCompilationFailed { index, ref source } => write!(
+                f,
+                "The pattern at index {} of the set failed to compile: {}",
+                index, source
+            ),
+            Error::ConverterNotFound(ref c) => write!(
+                f,
+                "The given converter name \"{}\" could not be found in the converter map",
+                c
+            ),
+            Error::DeserializationFailed {
+                field: Some(ref field),
+                ref message,
+            } => write!(f, "Could not deserialize field \"{}\": {}", field, message),
+            Error::DeserializationFailed {
+                field: None,
+                ref message,
+            } => write!(f, "Could not deserialize the matches: {}", message),
+            Error::PatternFileReadFailed {
+                file: Some(ref file),
+                ref reason,
+            } => write!(
+                f,
+                "Could not read the pattern file \"{}\": {}",
+                file, reason
+            ),
+            Error::PatternFileReadFailed {
+                file: None,
+                ref reason,
+            } => write!(f, "Could not read the patterns: {}", reason),
+            Error::PatternFileMalformed {
+                file: Some(ref file),
+                line,
+                ref reason,
+            } => write!(
+                f,
+                "Line {} of the pattern file \"{}\" is malformed: {}",
+                line, file, reason
+            ),
+            Error::PatternFileMalformed {
+                file: None,
+                line,
+                ref reason,
+            } => write!(f, "Line {} of the patterns is malformed: {}", line, reason),
+            Error::InvalidFieldName(ref n) => write!(
+                f,
+                "The field name \"{}\" is neither a plain name nor a field reference like \"[source][address]\"",
+                n
+            ),
+            Error::UnsupportedRegexConstruct {
+                ref engine,
+                ref construct,
+                ref regex,
+            } => write!(
+                f,
+                "The regex engine \"{}\" does not support the {} used in the regex \"{}\"",
+                engine, construct, regex
+            ),
+            Error::NestedFieldConflict {
+                ref field,
+                ref other,
+            } => write!(
+                f,
+                "The field \"{}\" cannot be nested because its path conflicts with the field \"{}\"",
+                field, other
+            ),
+            Error::InvalidReference {
+                ref reference,
+                pattern: Some(ref pattern),
+                offset,
+                ref source,
+            } => write!(
+                f,
+                "The reference \"{}\" at byte {} of the pattern \"{}\" is invalid: {}",
+                reference, offset, pattern, source
+            ),
+            Error::InvalidReference {
+                ref reference,
+                pattern: None,
+                offset,
+                ref source,
+            } => write!(
+                f,
+                "The reference \"{}\" at byte {} is invalid: {}",
+                reference, offset, source
+            ),
+            Error::ReferenceCycle(ref chain) => write!(
+                f,
+                "The patterns refer to each other in a cycle: {}",
+                chain.join(" -> ")
+            ),
+            Error::DefinitionsNotFound(ref missing) => {
+                f.write_str("The definitions of the patterns referenced by ")?;
+                for (i, m) in missing.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", m)?;
+                }
+                f.write_str(" could not be found")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::bool_assert_comparison,
+    clippy::expect_fun_call,
+    clippy::useless_conversion
+)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_simple_anonymous_pattern() {
+        let mut grok = Grok::empty();
+        grok.add_pattern("USERNAME", r"[a-zA-Z0-9._-]+");
+        let pattern = grok
+            .compile("%{USERNAME}", false)
+            .expect("Error while compiling!");
+
+        let matches = pattern.match_against("root").expect("No matches found!");
+        assert_eq!("root", matches.get("USERNAME").unwrap());
+        assert_eq!(1, matches.len());
+        let matches = pattern
             .match_against("john doe")
             .expect("No matches found!");
         assert_eq!("john", matches.get("USERNAME").unwrap());
@@ -607,6 +1698,8 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "default-patterns")]
+    #[cfg(any(feature = "onig", feature = "fancy-regex"))]
     fn test_loaded_default_patterns() {
         let mut grok = Grok::with_default_patterns();
         let pattern = grok
@@ -623,6 +1716,8 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "default-patterns")]
+    #[cfg(feature = "onig")]
     fn test_compilation_of_all_default_patterns() {
         let mut grok = Grok::default();
         let mut num_checked = 0;
@@ -637,6 +1732,31 @@ mod tests {
         assert!(num_checked > 0);
     }
 
+    #[test]
+    #[cfg(feature = "default-patterns")]
+    #[cfg(not(any(feature = "onig", feature = "fancy-regex")))]
+    fn test_default_patterns_name_unsupported_constructs() {
+        let mut grok = Grok::default();
+        for &(key, _) in PATTERNS {
+            match grok.compile(&format!("%{{{}}}", key), false) {
+                Ok(_) | Err(Error::UnsupportedRegexConstruct { .. }) => {}
+                Err(e) => panic!("Pattern {} failed to compile: {:?}", key, e),
+            }
+        }
+
+        for (key, construct) in [
+            ("RUUID", "hex digit class `\\h` / `\\H`"),
+            ("RPROCESSING", "literal `{` outside a repetition `{n,m}`"),
+        ] {
+            match grok.compile(&format!("%{{{}}}", key), false) {
+                Err(Error::UnsupportedRegexConstruct { construct: c, .. }) => {
+                    assert_eq!(construct, c)
+                }
+                r => panic!("{}: {:?}", key, r.map(|_| ())),
+            }
+        }
+    }
+
     #[test]
     fn test_adhoc_pattern() {
         let mut grok = Grok::default();
@@ -686,4 +1806,904 @@ mod tests {
         let actual = pattern.capture_names().collect::<Vec<_>>();
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    #[cfg(feature = "default-patterns")]
+    #[cfg(any(feature = "onig", feature = "fancy-regex"))]
+    fn test_prefilter() {
+        let mut grok = Grok::default();
+        let pattern = grok
+            .compile(r"%{TIMESTAMP_ISO8601:timestamp} \[%{IPV4:ip}:%{WORD:environment}\] %{LOGLEVEL:log_level} %{GREEDYDATA:message}", false)
+            .expect("Error while compiling!");
+        assert!(pattern.has_prefilter());
+        let matches = pattern
+            .match_against("2016-09-19T18:19:00 [8.8.8.8:prd] DEBUG this is an example log message")
+            .expect("No matches found!");
+        assert_eq!("prd", matches.get("environment").unwrap());
+        assert!(pattern
+            .match_against("2016-09-19T18:19:00 8.8.8.8:prd DEBUG this is an example log message")
+            .is_none());
+
+        let pattern = grok
+            .compile("%{WORD:a}|%{INT:b}", false)
+            .expect("Error while compiling!");
+        assert!(!pattern.has_prefilter());
+    }
+
+    #[test]
+    #[cfg(feature = "default-patterns")]
+    fn test_capture_conversion() {
+        let mut grok = Grok::default();
+        let pattern = grok
+            .compile("%{WORD:verb} %{INT:status:int}", true)
+            .expect("Error while compiling!");
+
+        assert_eq!(Some("int"), pattern.capture_conversion("status"));
+        assert_eq!(None, pattern.capture_conversion("verb"));
+        assert_eq!(None, pattern.capture_conversion("unknown"));
+    }
+
+    #[test]
+    #[cfg(feature = "default-patterns")]
+    #[cfg(any(feature = "onig", feature = "fancy-regex"))]
+    fn test_typed_values() {
+        let mut grok = Grok::default();
+        let pattern = grok
+            .compile(
+                "%{WORD:name} %{INT:bytes:int} %{NUMBER:duration:float} %{WORD:cached:bool}",
+                true,
+            )
+            .expect("Error while compiling!");
+
+        let matches = pattern
+            .match_against("index 1024 0.25 true")
+            .expect("No matches found!");
+        assert_eq!("1024", matches.get("bytes").unwrap());
+        assert_eq!(None, matches.get("bytes:int"));
+        assert_eq!(
+            Value::Str("index"),
+            matches.get_typed("name").unwrap().unwrap()
+        );
+        assert_eq!(
+            Value::Int(1024),
+            matches.get_typed("bytes").unwrap().unwrap()
+        );
+        assert_eq!(
+            Value::Float(0.25),
+            matches.get_typed("duration").unwrap().unwrap()
+        );
+        assert_eq!(
+            Value::Bool(true),
+            matches.get_typed("cached").unwrap().unwrap()
+        );
+        assert!(matches.get_typed("unknown").is_none());
+
+        let typed = matches
+            .iter_typed()
+            .map(|(k, v)| (k, v.unwrap()))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(4, typed.len());
+        assert_eq!(Value::Int(1024), typed["bytes"]);
+    }
+
+    #[test]
+    #[cfg(feature = "default-patterns")]
+    fn test_typed_value_conversion_failure() {
+        let mut grok = Grok::default();
+        let pattern = grok
+            .compile("%{WORD:bytes:int}", false)
+            .expect("Error while compiling!");
+
+        let matches = pattern.match_against("many").expect("No matches found!");
+        assert_eq!("many", matches.get("bytes").unwrap());
+        assert_eq!(
+            Error::ConversionFailed {
+                field: "bytes".into(),
+                value: "many".into(),
+                conversion: "int".into(),
+                reason: "invalid digit found in string".into(),
+            },
+            matches.get_typed("bytes").unwrap().unwrap_err()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "default-patterns")]
+    #[cfg(any(feature = "onig", feature = "fancy-regex"))]
+    fn test_custom_converters() {
+        use std::net::IpAddr;
+
+        let mut grok = Grok::default();
+        grok.add_converter("ip", |v: &str| {
+            v.parse::<IpAddr>()
+                .map(Value::custom)
+                .map_err(|e| e.to_string())
+        });
+        grok.add_converter("duration_ms", |v: &str| {
+            v.parse::<f64>()
+                .map(|ms| Value::Float(ms / 1000.0))
+                .map_err(|e| e.to_string())
+        });
+        let pattern = grok
+            .compile("%{IP:client:ip} %{NUMBER:dur:duration_ms}", true)
+            .expect("Error while compiling!");
+
+        let matches = pattern
+            .match_against("127.0.0.1 1500")
+            .expect("No matches found!");
+        let client = matches.get_typed("client").unwrap().unwrap();
+        assert_eq!(
+            Some(&IpAddr::from([127, 0, 0, 1])),
+            client.downcast_ref::<IpAddr>()
+        );
+        assert_eq!(Value::custom(IpAddr::from([127, 0, 0, 1])), client);
+        assert_eq!(
+            Value::Float(1.5),
+            matches.get_typed("dur").unwrap().unwrap()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "default-patterns")]
+    fn test_unknown_converter() {
+        let mut grok = Grok::default();
+        assert_eq!(
+            Error::InvalidReference {
+                reference: "%{HTTPDATE:ts:timestamp}".into(),
+                pattern: None,
+                offset: 0,
+                source: Box::new(Error::ConverterNotFound("timestamp".into())),
+            },
+            grok.compile("%{HTTPDATE:ts:timestamp}", false).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_invalid_reference_in_definition() {
+        let mut grok = Grok::empty();
+        grok.add_pattern("WORD", r"\w+");
+        grok.add_pattern("REQUEST", r"%{WORD:verb} %{WORD:path:url}");
+        assert_eq!(
+            Error::InvalidReference {
+                reference: "%{WORD:path:url}".into(),
+                pattern: Some("REQUEST".into()),
+                offset: 13,
+                source: Box::new(Error::ConverterNotFound("url".into())),
+            },
+            grok.compile("^%{REQUEST}$", false).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_compile_with_options() {
+        let mut grok = Grok::empty();
+        grok.add_pattern("WORD", r"\w+");
+        grok.add_pattern("METHOD", "GET|POST");
+        grok.add_pattern("OPT", r"\w*");
+        grok.add_pattern("INT", r"\d+");
+
+        let options = CompileOptions::new().alias_only(true).anchored(true);
+        let pattern = grok
+            .compile_with("%{WORD:verb} %{WORD}", &options)
+            .expect("Error while compiling!");
+        assert!(pattern.match_against("get index").is_some());
+        assert!(pattern.match_against("get index now").is_none());
+        assert!(pattern.match_against("get index\nbad").is_none());
+        assert!(pattern.match_against("bad\nget index").is_none());
+        assert_eq!(vec!["verb"], pattern.capture_names().collect::<Vec<_>>());
+
+        // Without the option, `^` and `$` in a pattern still match at line boundaries.
+        let pattern = grok
+            .compile_with("^%{INT:n}$", &CompileOptions::new())
+            .expect("Error while compiling!");
+        assert_eq!(
+            "42",
+            pattern.match_against("bad\n42").unwrap().get("n").unwrap()
+        );
+
+        let options = CompileOptions::new().case_insensitive(true);
+        let pattern = grok
+            .compile_with("%{METHOD:verb} /", &options)
+            .expect("Error while compiling!");
+        assert!(!pattern.has_prefilter());
+        assert_eq!(
+            "get",
+            pattern.match_against("get /").unwrap().get("verb").unwrap()
+        );
+
+        let options = CompileOptions::new()
+            .dot_matches_new_line(true)
+            .ignore_whitespace(true);
+        let pattern = grok
+            .compile_with("%{WORD:first} . %{WORD:second}", &options)
+            .expect("Error while compiling!");
+        let matches = pattern.match_against("a\nb").expect("No matches found!");
+        assert_eq!("b", matches.get("second").unwrap());
+
+        let pattern = grok
+            .compile_with("%{WORD:a}-%{OPT:b}", &CompileOptions::new())
+            .expect("Error while compiling!");
+        assert_eq!(Some(""), pattern.match_against("x-").unwrap().get("b"));
+        let options = CompileOptions::new().keep_empty_captures(false);
+        let pattern = grok
+            .compile_with("%{WORD:a}-%{OPT:b}", &options)
+            .expect("Error while compiling!");
+        let matches = pattern.match_against("x-").expect("No matches found!");
+        assert_eq!(None, matches.get("b"));
+        assert_eq!(vec![("a", "x")], matches.iter().collect::<Vec<_>>());
+
+        let options = CompileOptions::new().fields(["b"]);
+        let pattern = grok
+            .compile_with("%{WORD:a}-%{OPT:b}", &options)
+            .expect("Error while compiling!");
+        assert_eq!(vec!["b"], pattern.capture_names().collect::<Vec<_>>());
+
+        let options = CompileOptions::new().convert_types(false);
+        let pattern = grok
+            .compile_with("%{INT:n:int} %{INT:m:unknown}", &options)
+            .expect("Error while compiling!");
+        let matches = pattern.match_against("1 2").expect("No matches found!");
+        assert_eq!(Value::Str("1"), matches.get_typed("n").unwrap().unwrap());
+        assert_eq!(None, pattern.capture_conversion("m"));
+    }
+
+    #[test]
+    fn test_regex_compilation_failure() {
+        let mut grok = Grok::empty();
+        grok.add_pattern("WORD", r"\w+");
+        grok.add_pattern("QS", r#"("[^"]*""#);
+        grok.add_pattern("LOG", "%{WORD:verb} %{QS:referrer}");
+
+        let error = grok.compile("^%{LOG}$", false).unwrap_err();
+        match error {
+            Error::RegexCompilationFailed {
+                ref reference,
+                ref pattern,
+                ref source,
+                ..
+            } => {
+                assert_eq!(Some("%{QS:referrer}"), reference.as_deref());
+                assert_eq!(Some("LOG"), pattern.as_deref());
+                assert_eq!(engine::Regex::NAME, source.engine());
+                assert_eq!(cfg!(feature = "onig"), source.code().is_some());
+            }
+            ref e => panic!("{:?}", e),
+        }
+        assert!(error
+            .to_string()
+            .starts_with("The regex failed compilation in the underlying engine inside \"%{QS:referrer}\" from \"LOG\": "));
+        let source = error.source().expect("No source!");
+        assert!(source.downcast_ref::<EngineError>().is_some());
+
+        match grok.compile("%{QS}", false).unwrap_err() {
+            Error::RegexCompilationFailed {
+                reference, pattern, ..
+            } => {
+                assert_eq!(Some("%{QS}"), reference.as_deref());
+                assert_eq!(None, pattern);
+            }
+            e => panic!("{:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_missing_definitions() {
+        let mut grok = Grok::empty();
+        grok.add_pattern("WORD", r"\w+");
+        grok.add_pattern("IPV4", r"\d+\.\d+\.\d+\.\d+");
+        grok.add_pattern("IPV6", r"[0-9a-f:]+");
+        grok.add_pattern("REQUEST", r"%{WROD:verb} %{URIPATH:path}");
+        let missing =
+            |name: &str, reference: &str, pattern: Option<&str>, offset, suggestions: &[&str]| {
+                MissingDefinition {
+                    name: name.into(),
+                    reference: reference.into(),
+                    pattern: pattern.map(String::from),
+                    offset,
+                    suggestions: suggestions.iter().map(|s| s.to_string()).collect(),
+                }
+            };
+
+        let error = grok
+            .compile("%{IPV4ADDR:client} %{REQUEST} %{REQUEST}", false)
+            .unwrap_err();
+        assert_eq!(
+            Error::DefinitionsNotFound(vec![
+                missing("IPV4ADDR", "%{IPV4ADDR:client}", None, 0, &["IPV4"]),
+                missing("WROD", "%{WROD:verb}", Some("REQUEST"), 0, &["WORD"]),
+                missing("URIPATH", "%{URIPATH:path}", Some("REQUEST"), 13, &[]),
+            ]),
+            error
+        );
+        assert_eq!(
+            "The definitions of the patterns referenced by \"%{IPV4ADDR:client}\" at byte 0 (did you mean IPV4?), \
+             \"%{WROD:verb}\" at byte 0 of the pattern \"REQUEST\" (did you mean WORD?), \
+             \"%{URIPATH:path}\" at byte 13 of the pattern \"REQUEST\" could not be found",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn test_reference_cycle() {
+        let mut grok = Grok::empty();
+        grok.add_pattern("A", "a%{B}");
+        grok.add_pattern("B", "b%{C:c}|%{A}");
+        grok.add_pattern("C", "c");
+        assert_eq!(
+            Error::ReferenceCycle(vec!["A".into(), "B".into(), "A".into()]),
+            grok.compile("^%{A}$", false).unwrap_err()
+        );
+        assert_eq!(
+            "The patterns refer to each other in a cycle: B -> A -> B",
+            grok.compile("%{B}", false).unwrap_err().to_string()
+        );
+
+        grok.add_pattern("SELF", "%{SELF}");
+        assert_eq!(
+            Error::ReferenceCycle(vec!["SELF".into(), "SELF".into()]),
+            grok.compile("%{SELF}", false).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_check_cycles() {
+        let mut grok = Grok::empty();
+        grok.add_pattern("A", "a%{B}%{B}");
+        grok.add_pattern("B", "b%{C}%{UNDEFINED}");
+        grok.add_pattern("C", "c");
+        assert_eq!(Ok(()), grok.check_cycles());
+
+        grok.add_pattern("C", "c%{D=d}|%{E}");
+        grok.add_pattern("E", "%{A}");
+        assert_eq!(
+            Err(Error::ReferenceCycle(vec![
+                "A".into(),
+                "B".into(),
+                "C".into(),
+                "E".into(),
+                "A".into()
+            ])),
+            grok.check_cycles()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "default-patterns")]
+    fn test_default_patterns_have_no_cycles() {
+        assert_eq!(Ok(()), Grok::default().check_cycles());
+    }
+
+    #[test]
+    fn test_inline_definition() {
+        let mut grok = Grok::empty();
+        let pattern = grok
+            .compile(r"%{NUM:n:int=\d+}-%{NUM:m}", true)
+            .expect("Error while compiling!");
+        let matches = pattern.match_against("12-34").expect("No matches found!");
+        assert_eq!(Value::Int(12), matches.get_typed("n").unwrap().unwrap());
+        assert_eq!("34", matches.get("m").unwrap());
+        assert_eq!(Some(&r"\d+".to_string()), grok.patterns.get("NUM"));
+    }
+
+    #[test]
+    #[cfg(any(feature = "onig", feature = "fancy-regex"))]
+    fn test_expansion_is_not_rescanned() {
+        let mut grok = Grok::empty();
+        grok.add_pattern("PERCENT", "%");
+        grok.add_pattern("WORD", r"\w+");
+        let pattern = grok
+            .compile("%{PERCENT}{WORD}", false)
+            .expect("Error while compiling!");
+        assert!(pattern.match_against("%{WORD}").is_some());
+        assert!(pattern.match_against("%{word}").is_none());
+    }
+
+    #[test]
+    fn test_unsupported_regex_construct() {
+        let mut grok = Grok::empty();
+        grok.add_pattern("NUMERIC", r"(?<![0-9.])[0-9]+");
+        grok.add_pattern("LOOSE", r"\w+.?*\w+");
+
+        let result = grok.compile("%{NUMERIC:num}", true);
+        if cfg!(any(feature = "onig", feature = "fancy-regex")) {
+            assert!(result.is_ok());
+        } else {
+            assert_eq!(
+                Err(Error::UnsupportedRegexConstruct {
+                    engine: "regex".into(),
+                    construct: "negative lookbehind `(?<!...)`".into(),
+                    regex: r"(?<grok0>(?<![0-9.])[0-9]+)".into(),
+                }),
+                result.map(|_| ())
+            );
+        }
+
+        match grok.compile("%{LOOSE:value}", true) {
+            Ok(_) => assert!(cfg!(feature = "onig")),
+            Err(Error::UnsupportedRegexConstruct { construct, .. }) => {
+                assert_eq!("nested quantifier like `.?*`", construct)
+            }
+            Err(e) => panic!("{:?}", e),
+        }
+    }
+
+    #[test]
+    #[cfg(all(
+        any(feature = "onig", feature = "fancy-regex"),
+        feature = "patterns-grok",
+        feature = "patterns-httpd"
+    ))]
+    fn test_with_pattern_packs() {
+        let mut grok = Grok::with_pattern_packs(&[Pack::Grok, Pack::Httpd]);
+        assert!(patterns_in(Pack::Httpd)
+            .iter()
+            .chain(patterns_in(Pack::Grok))
+            .all(|&(key, value)| grok.patterns.get(key).map(|v| v.as_str()) == Some(value)));
+        assert!(patterns_in(Pack::Java)
+            .iter()
+            .all(|&(key, _)| !grok.patterns.contains_key(key)));
+
+        let pattern = grok
+            .compile("%{HTTPD_COMMONLOG}", false)
+            .expect("Error while compiling!");
+        let matches = pattern
+            .match_against(r#"127.0.0.1 - - [11/Dec/2013:00:01:45 -0800] "GET /xampp/status.php HTTP/1.1" 200 3891"#)
+            .expect("No matches found!");
+        assert_eq!("GET", matches.get("verb").unwrap());
+        match grok.compile("%{JAVACLASS}", false) {
+            Err(Error::DefinitionsNotFound(missing)) => {
+                assert_eq!(1, missing.len());
+                assert_eq!("JAVACLASS", missing[0].name);
+            }
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_patterns_in() {
+        let mut total = 0;
+        for &pack in Pack::all() {
+            let patterns = patterns_in(pack);
+            assert_eq!(pack.is_enabled(), !patterns.is_empty());
+            total += patterns.len();
+        }
+        assert_eq!(patterns().len(), total);
+        assert_eq!("linux-syslog", Pack::LinuxSyslog.name());
+    }
+
+    #[test]
+    fn test_add_patterns_from_reader() {
+        let mut grok = Grok::empty();
+        let patterns = "# Users\n\nUSERNAME [a-zA-Z0-9._-]+\r\nUSER %{USERNAME}\n";
+        grok.add_patterns_from_reader(patterns.as_bytes())
+            .expect("Error while reading!");
+        let pattern = grok
+            .compile("%{USER:usr}", true)
+            .expect("Error while compiling!");
+
+        let matches = pattern.match_against("root").expect("No matches found!");
+        assert_eq!("root", matches.get("usr").unwrap());
+    }
+
+    #[test]
+    fn test_add_patterns_from_malformed_reader() {
+        let mut grok = Grok::empty();
+        let patterns = "USERNAME [a-zA-Z0-9._-]+\n# Users\nUSER\n";
+        assert_eq!(
+            Err(Error::PatternFileMalformed {
+                file: None,
+                line: 3,
+                reason: "missing definition for the pattern \"USER\"".into(),
+            }),
+            grok.add_patterns_from_reader(patterns.as_bytes())
+        );
+        assert_eq!(
+            Error::DefinitionsNotFound(vec![MissingDefinition {
+                name: "USERNAME".into(),
+                reference: "%{USERNAME}".into(),
+                pattern: None,
+                offset: 0,
+                suggestions: vec![],
+            }]),
+            grok.compile("%{USERNAME}", false).unwrap_err()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "default-patterns")]
+    fn test_add_patterns_from_dir() {
+        let mut grok = Grok::empty();
+        grok.add_patterns_from_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/patterns"))
+            .expect("Error while reading!");
+        assert_eq!(Grok::with_default_patterns().patterns, grok.patterns);
+    }
+
+    #[test]
+    fn test_add_patterns_from_malformed_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.pattern"), "USERNAME [a-z]+\n").unwrap();
+        std::fs::write(dir.path().join("b.pattern"), "# b\nUSER-NAME [a-z]+\n").unwrap();
+        std::fs::write(dir.path().join("README.md"), "Not a pattern file\n").unwrap();
+
+        let mut grok = Grok::empty();
+        grok.add_patterns_from_file(dir.path().join("a.pattern"))
+            .expect("Error while reading!");
+        assert!(grok.compile("%{USERNAME}", false).is_ok());
+
+        match grok.add_patterns_from_dir(dir.path()) {
+            Err(Error::PatternFileMalformed { file, line, .. }) => {
+                assert_eq!(
+                    Some(dir.path().join("b.pattern").display().to_string()),
+                    file
+                );
+                assert_eq!(2, line);
+            }
+            other => panic!("{:?}", other),
+        }
+
+        match grok.add_patterns_from_file(dir.path().join("c.pattern")) {
+            Err(Error::PatternFileReadFailed { file, .. }) => {
+                assert_eq!(
+                    Some(dir.path().join("c.pattern").display().to_string()),
+                    file
+                )
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_bracketed_field_names() {
+        let mut grok = Grok::empty();
+        grok.add_pattern("WORD", r"\w+");
+        grok.add_pattern("INT", r"\d+");
+        let pattern = grok
+            .compile(
+                "%{WORD:[http][verb]} %{INT:[http][status]:int} %{WORD:src-host}",
+                true,
+            )
+            .expect("Error while compiling!");
+
+        let matches = pattern
+            .match_against("GET 200 localhost")
+            .expect("No matches found!");
+        assert_eq!("GET", matches.get("[http][verb]").unwrap());
+        assert_eq!(
+            Value::Int(200),
+            matches.get_typed("[http][status]").unwrap().unwrap()
+        );
+        assert_eq!("localhost", matches.get("src-host").unwrap());
+
+        for name in [
+            "[http",
+            "http]",
+            "[http][]",
+            "[http]verb",
+            "http[verb]",
+            "[]",
+        ] {
+            let reference = format!("%{{WORD:{}}}", name);
+            assert_eq!(
+                Err(Error::InvalidReference {
+                    reference: reference.clone(),
+                    pattern: None,
+                    offset: 0,
+                    source: Box::new(Error::InvalidFieldName(name.into())),
+                }),
+                grok.compile(&reference, true).map(|_| ())
+            );
+        }
+    }
+
+    #[test]
+    fn test_to_nested() {
+        let mut grok = Grok::empty();
+        grok.add_pattern("WORD", r"\w+");
+        grok.add_pattern("INT", r"\d+");
+        let pattern = grok
+            .compile(
+                "%{WORD:[http][verb]} %{INT:http.status:int} %{WORD:host}",
+                true,
+            )
+            .expect("Error while compiling!");
+        let matches = pattern
+            .match_against("GET 200 localhost")
+            .expect("No matches found!");
+
+        let nested = matches.to_nested().expect("Nesting failed!");
+        assert_eq!(
+            Some(&Value::Str("GET")),
+            nested.get(&["http", "verb"]).and_then(Nested::as_value)
+        );
+        assert_eq!(
+            Some(&Value::Int(200)),
+            nested.get(&["http", "status"]).and_then(Nested::as_value)
+        );
+        assert_eq!(
+            Some(&Value::Str("localhost")),
+            nested.get(&["host"]).and_then(Nested::as_value)
+        );
+        assert_eq!(
+            2,
+            nested
+                .get(&["http"])
+                .and_then(Nested::as_map)
+                .unwrap()
+                .len()
+        );
+
+        let pattern = grok
+            .compile("%{WORD:http} %{INT:http.status}", true)
+            .expect("Error while compiling!");
+        let matches = pattern.match_against("GET 200").expect("No matches found!");
+        assert_eq!(
+            Err(Error::NestedFieldConflict {
+                field: "http.status".into(),
+                other: "http".into(),
+            }),
+            matches.to_nested()
+        );
+    }
+
+    #[test]
+    fn test_repeated_name_returns_participating_group() {
+        let mut grok = Grok::empty();
+        grok.add_pattern("IPV4", r"\d+\.\d+\.\d+\.\d+");
+        grok.add_pattern("HOSTNAME", r"[a-z.]+");
+        let pattern = grok
+            .compile("^(?:%{IPV4:addr}|%{HOSTNAME:addr})$", true)
+            .expect("Error while compiling!");
+
+        let matches = pattern
+            .match_against("10.0.0.1")
+            .expect("No matches found!");
+        assert_eq!(Some("10.0.0.1"), matches.get("addr"));
+        assert_eq!(1, matches.iter().count());
+        let matches = pattern
+            .match_against("example.com")
+            .expect("No matches found!");
+        assert_eq!(Some("example.com"), matches.get("addr"));
+        assert_eq!(
+            vec![("addr", "example.com")],
+            matches.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(vec!["example.com"], matches.get_all("addr"));
+        assert_eq!(Vec::<&str>::new(), matches.get_all("IPV4"));
+    }
+
+    #[test]
+    fn test_raw_named_groups() {
+        let mut grok = Grok::empty();
+        grok.add_pattern("WORD", r"\w+");
+        grok.add_pattern("KV", r"(?<key>\w+)=(?<grok0>\w+)");
+        let pattern = grok
+            .compile("%{WORD:name0} %{KV} (?<grok1>%{WORD:grok2})", true)
+            .expect("Error while compiling!");
+        assert_eq!(
+            vec!["grok0", "grok1", "grok2", "key", "name0"],
+            pattern.capture_names().collect::<Vec<_>>()
+        );
+        let matches = pattern.match_against("a b=c d").expect("No matches found!");
+        assert_eq!(Some("a"), matches.get("name0"));
+        assert_eq!(Some("b"), matches.get("key"));
+        assert_eq!(Some("c"), matches.get("grok0"));
+        assert_eq!(Some("d"), matches.get("grok1"));
+        assert_eq!(Some("d"), matches.get("grok2"));
+
+        // A named group and an alias with the same name are the same field.
+        let pattern = grok
+            .compile("(?<key>%{WORD}):|%{WORD:key}!", true)
+            .expect("Error while compiling!");
+        let matches = pattern.match_against("a!").expect("No matches found!");
+        assert_eq!(Some("a"), matches.get("key"));
+        assert_eq!(vec!["key"], pattern.capture_names().collect::<Vec<_>>());
+
+        let options = CompileOptions::new().fields(["value"]);
+        let pattern = grok
+            .compile_with("%{KV} (?<value>%{WORD})", &options)
+            .expect("Error while compiling!");
+        assert_eq!(vec!["value"], pattern.capture_names().collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[cfg(any(feature = "onig", feature = "fancy-regex"))]
+    fn test_raw_named_group_backreferences() {
+        let mut grok = Grok::empty();
+        grok.add_pattern("WORD", r"\w+");
+        grok.add_pattern("TAG", r"<(?<tag>\w+)>%{WORD:text}</\k<tag>>");
+        let pattern = grok
+            .compile("%{TAG} %{TAG}", true)
+            .expect("Error while compiling!");
+        let matches = pattern
+            .match_against("<a>x</a> <b>y</b>")
+            .expect("No matches found!");
+        assert_eq!(vec!["a", "b"], matches.get_all("tag"));
+        assert_eq!(vec!["x", "y"], matches.get_all("text"));
+        assert!(pattern.match_against("<a>x</b> <b>y</b>").is_none());
+
+        // The other spellings of named groups, backreferences and subroutine calls.
+        for (regex, text) in [
+            (r"(?<x>a)\g<x>", "aa"),
+            (r"(?<x>a)\k'x'", "aa"),
+            (r"(?<x>a)\g'x'", "aa"),
+            (r"(?'x'a)b", "ab"),
+        ] {
+            let pattern = grok.compile(regex, true).expect("Error while compiling!");
+            let matches = pattern.match_against(text).expect("No matches found!");
+            assert_eq!(Some("a"), matches.get("x"), "{}", regex);
+        }
+    }
+
+    #[test]
+    fn test_spans() {
+        let mut grok = Grok::empty();
+        grok.add_pattern("WORD", r"\w+");
+        grok.add_pattern("INT", r"\d+");
+        let pattern = grok
+            .compile("%{WORD:verb} (?:%{INT:status}|%{WORD:status})", true)
+            .expect("Error while compiling!");
+        let text = "> GET ok <";
+        let matches = pattern.match_against(text).expect("No matches found!");
+
+        assert_eq!(2..8, matches.match_span());
+        assert_eq!(Some(2..5), matches.span("verb"));
+        assert_eq!(Some(6..8), matches.span("status"));
+        assert_eq!(None, matches.span("missing"));
+        assert_eq!(
+            vec![("status", "ok", 6..8), ("verb", "GET", 2..5)],
+            matches.iter_spans().collect::<Vec<_>>()
+        );
+        for (_, value, span) in matches.iter_spans() {
+            assert_eq!(value, &text[span]);
+        }
+    }
+
+    #[test]
+    fn test_captures_iter() {
+        let mut grok = Grok::empty();
+        grok.add_pattern("IPV4", r"\d+\.\d+\.\d+\.\d+");
+        let pattern = grok
+            .compile("%{IPV4:ip}", true)
+            .expect("Error while compiling!");
+        let text = "from 10.0.0.1 via 10.0.0.2 to 192.168.0.1";
+
+        let ips = pattern
+            .captures_iter(text)
+            .map(|m| (m.get("ip").unwrap().to_string(), m.span("ip").unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("10.0.0.1".to_string(), 5..13),
+                ("10.0.0.2".to_string(), 18..26),
+                ("192.168.0.1".to_string(), 30..41),
+            ],
+            ips
+        );
+        assert_eq!(
+            vec![5..13, 18..26, 30..41],
+            pattern.find_iter(text).collect::<Vec<_>>()
+        );
+        assert_eq!(0, pattern.find_iter("no address").count());
+        assert_eq!(0, pattern.find_iter("").count());
+    }
+
+    #[test]
+    fn test_captures_iter_empty_matches() {
+        let mut grok = Grok::empty();
+        grok.add_pattern("DIGITS", r"\d*");
+        let pattern = grok
+            .compile("%{DIGITS:n}", true)
+            .expect("Error while compiling!");
+
+        // The empty matches right after `12` and `3` are skipped.
+        assert_eq!(
+            vec![0..2, 4..5, 7..7],
+            pattern.find_iter("12ä3ö").collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![Some("12"), Some("3"), Some("")],
+            pattern
+                .captures_iter("12ä3ö")
+                .map(|m| m.get("n").map(str::to_string))
+                .collect::<Vec<_>>()
+                .iter()
+                .map(Option::as_deref)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(vec![0..0, 2..2], pattern.find_iter("ä").collect::<Vec<_>>());
+        assert_eq!(vec![0..0], pattern.find_iter("").collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_get_all() {
+        let mut grok = Grok::empty();
+        grok.add_pattern("WORD", r"\w+");
+        let pattern = grok
+            .compile("%{WORD} %{WORD} %{WORD}(?: %{WORD})?", false)
+            .expect("Error while compiling!");
+        let matches = pattern
+            .match_against("one two three")
+            .expect("No matches found!");
+        assert_eq!(vec!["one", "two", "three"], matches.get_all("WORD"));
+        assert_eq!(Some("three"), matches.get("WORD"));
+    }
+
+    #[test]
+    #[cfg(all(feature = "onig", feature = "default-patterns"))]
+    fn test_httpd_repeated_names() {
+        let mut grok = Grok::with_default_patterns();
+        let pattern = grok
+            .compile("%{HTTPD_COMMONLOG}", false)
+            .expect("Error while compiling!");
+        let matches = pattern
+            .match_against(r#"127.0.0.1 frank@example.com - [10/Oct/2000:13:55:36 -0700] "GET /apache_pb.gif HTTP/1.0" 200 2326"#)
+            .expect("No matches found!");
+
+        // HOSTNAME is part of both IPORHOST and EMAILADDRESS, but only the latter matched.
+        assert_eq!(Some("example.com"), matches.get("HOSTNAME"));
+        assert_eq!(Some("frank@example.com"), matches.get("ident"));
+        assert_eq!(Some("frank@example.com"), matches.get("EMAILADDRESS"));
+        // USER is part of both HTTPDUSER references, but only the auth one matched.
+        assert_eq!(Some("-"), matches.get("USER"));
+        assert_eq!(Some("127.0.0.1"), matches.get("clientip"));
+    }
+
+    #[test]
+    #[cfg(all(feature = "onig", feature = "default-patterns"))]
+    fn test_ecs_v1_patterns() {
+        let line = r#"127.0.0.1 - frank [11/Dec/2013:00:01:45 -0800] "GET /xampp/status.php HTTP/1.1" 200 3891 "http://cadenza/xampp/navi.php" "Mozilla/5.0""#;
+
+        let mut grok = Grok::with_default_patterns_compat(Compat::EcsV1);
+        for &(key, _) in ECS_V1_PATTERNS {
+            grok.compile(&format!("%{{{}}}", key), false)
+                .unwrap_or_else(|e| panic!("Pattern {} failed to compile: {}", key, e));
+            // Every ECS variant belongs to a pack which is documented as supported.
+            assert!(Pack::all()
+                .iter()
+                .filter(|p| p.supports(Compat::EcsV1))
+                .any(|&p| patterns_in(p).iter().any(|&(k, _)| k == key)));
+        }
+        assert_eq!(
+            vec![Pack::Grok, Pack::Httpd, Pack::LinuxSyslog],
+            Pack::all()
+                .iter()
+                .copied()
+                .filter(|p| p.supports(Compat::EcsV1))
+                .collect::<Vec<_>>()
+        );
+        assert!(Pack::all().iter().all(|p| p.supports(Compat::Legacy)));
+        // The packs without an ECS variant are left out instead of mixing in legacy names.
+        assert!(matches!(
+            grok.compile("%{JAVASTACKTRACEPART}", false),
+            Err(Error::DefinitionsNotFound(_))
+        ));
+        assert!(grok.compile("%{SYSLOGLINE}", false).is_ok());
+        let pattern = grok
+            .compile("%{HTTPD_COMBINEDLOG}", true)
+            .expect("Error while compiling!");
+        let matches = pattern.match_against(line).expect("No matches found!");
+        assert_eq!("127.0.0.1", matches.get("[source][address]").unwrap());
+        assert_eq!("frank", matches.get("[user][name]").unwrap());
+        assert_eq!("GET", matches.get("[http][request][method]").unwrap());
+        assert_eq!(
+            Value::Int(200),
+            matches
+                .get_typed("[http][response][status_code]")
+                .unwrap()
+                .unwrap()
+        );
+        assert_eq!(
+            "Mozilla/5.0",
+            matches.get("[user_agent][original]").unwrap()
+        );
+        assert_eq!(None, matches.get("clientip"));
+
+        let mut grok = Grok::with_default_patterns_compat(Compat::Legacy);
+        let pattern = grok
+            .compile("%{HTTPD_COMBINEDLOG}", true)
+            .expect("Error while compiling!");
+        let matches = pattern.match_against(line).expect("No matches found!");
+        assert_eq!("127.0.0.1", matches.get("clientip").unwrap());
+        assert_eq!(None, matches.get("[source][address]"));
+    }
 }
@@ -0,0 +1,356 @@
+use crate::parser::{self, Node};
+use crate::suggest;
+use crate::{Compat, Error, Grok, Pack};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::PathBuf;
+
+/// Where the definition of a pattern comes from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Source {
+    /// One of the default pattern files.
+    Pack(Pack),
+    /// The variant of a default pattern loaded by `Grok::with_default_patterns_compat`.
+    Compat(Compat),
+    /// A pattern file loaded at runtime.
+    File(PathBuf),
+    /// A reader passed to `Grok::add_patterns_from_reader`.
+    Reader,
+    /// An inline definition like `%{NUM=\d+}` in a compiled pattern.
+    Inline,
+    /// `Grok::add_pattern` or one of the `From` implementations.
+    Custom,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Source::Pack(pack) => write!(f, "{}.pattern", pack.name()),
+            Source::Compat(compat) => write!(f, "the {:?} patterns", compat),
+            Source::File(ref path) => write!(f, "{}", path.display()),
+            Source::Reader => f.write_str("a reader"),
+            Source::Inline => f.write_str("an inline definition"),
+            Source::Custom => f.write_str("add_pattern"),
+        }
+    }
+}
+
+/// A problem with the patterns of a `Grok` instance, found by `Grok::lint`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Finding {
+    /// A pattern refers to a pattern which is not defined.
+    UndefinedReference {
+        /// The name of the pattern holding the reference.
+        pattern: String,
+        /// The reference as written in the definition, like `%{IPV4ADDR:ip}`.
+        reference: String,
+        /// The byte offset of the reference in the definition.
+        offset: usize,
+        /// The names of defined patterns which are similar to the undefined one.
+        suggestions: Vec<String>,
+    },
+    /// A pattern has been defined more than once, so all but the last definition are
+    /// silently replaced.
+    Duplicate {
+        /// The name of the pattern.
+        pattern: String,
+        /// The sources of every definition in the order they were added, the last one wins.
+        sources: Vec<Source>,
+    },
+    /// Patterns refer to each other in a cycle, see `Error::ReferenceCycle`.
+    Cycle(Vec<String>),
+    /// A pattern is not used by any other pattern.
+    ///
+    /// This is expected for the patterns meant to be compiled directly, like
+    /// `HTTPD_COMBINEDLOG`, but may point to a typo in the name of a pattern.
+    Unused(String),
+    /// A pattern fails to compile although every pattern it refers to compiles.
+    CompilationFailed {
+        /// The name of the pattern.
+        pattern: String,
+        /// The error it fails to compile with.
+        error: Error,
+    },
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Finding::UndefinedReference {
+                ref pattern,
+                ref reference,
+                offset,
+                ref suggestions,
+            } => {
+                write!(
+                    f,
+                    "The pattern \"{}\" refers to an undefined pattern with \"{}\" at byte {}",
+                    pattern, reference, offset
+                )?;
+                if !suggestions.is_empty() {
+                    write!(f, " (did you mean {}?)", suggestions.join(", "))?;
+                }
+                Ok(())
+            }
+            Finding::Duplicate {
+                ref pattern,
+                ref sources,
+            } => {
+                write!(
+                    f,
+                    "The pattern \"{}\" is defined more than once, in ",
+                    pattern
+                )?;
+                for (i, source) in sources.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", source)?;
+                }
+                Ok(())
+            }
+            Finding::Cycle(ref chain) => write!(
+                f,
+                "The patterns refer to each other in a cycle: {}",
+                chain.join(" -> ")
+            ),
+            Finding::Unused(ref pattern) => write!(
+                f,
+                "The pattern \"{}\" is not used by any other pattern",
+                pattern
+            ),
+            Finding::CompilationFailed {
+                ref pattern,
+                ref error,
+            } => write!(f, "The pattern \"{}\" fails to compile: {}", pattern, error),
+        }
+    }
+}
+
+/// The findings of `Grok::lint`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LintReport {
+    findings: Vec<Finding>,
+}
+
+impl LintReport {
+    /// Returns all findings, grouped by their kind and ordered by the name of the pattern.
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    /// Returns true if nothing has been found, not even unused patterns.
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    /// Returns the findings which point to an actual problem, which are all but
+    /// `Finding::Unused`.
+    pub fn problems(&self) -> impl Iterator<Item = &Finding> {
+        self.findings
+            .iter()
+            .filter(|f| !matches!(f, Finding::Unused(_)))
+    }
+}
+
+impl fmt::Display for LintReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for finding in &self.findings {
+            writeln!(f, "{}", finding)?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks all the patterns of the `Grok` instance, see `Grok::lint`.
+pub(crate) fn lint(grok: &Grok) -> LintReport {
+    let mut findings = Vec::new();
+
+    // The patterns each pattern refers to, not counting inline definitions.
+    let mut references = BTreeMap::new();
+    for (name, definition) in &grok.patterns {
+        let mut refers_to = BTreeSet::new();
+        for node in parser::parse(definition) {
+            if let Node::Reference(r) = node {
+                if r.definition.is_some() {
+                    continue;
+                }
+                if !grok.patterns.contains_key(r.name) {
+                    findings.push(Finding::UndefinedReference {
+                        pattern: name.clone(),
+                        reference: r.text.into(),
+                        offset: r.offset,
+                        suggestions: suggest::similar_names(r.name, grok.patterns.keys()),
+                    });
+                }
+                refers_to.insert(r.name);
+            }
+        }
+        references.insert(name.as_str(), refers_to);
+    }
+
+    for (name, sources) in &grok.sources {
+        if sources.len() > 1 {
+            findings.push(Finding::Duplicate {
+                pattern: name.clone(),
+                sources: sources.clone(),
+            });
+        }
+    }
+
+    if let Err(Error::ReferenceCycle(chain)) = grok.check_cycles() {
+        findings.push(Finding::Cycle(chain));
+    }
+
+    let used = references
+        .iter()
+        .flat_map(|(name, refers_to)| refers_to.iter().filter(move |r| *r != name))
+        .collect::<BTreeSet<_>>();
+    for name in grok.patterns.keys() {
+        if !used.contains(&name.as_str()) {
+            findings.push(Finding::Unused(name.clone()));
+        }
+    }
+
+    let mut compiler = grok.clone();
+    let mut compiles = BTreeMap::new();
+    for name in grok.patterns.keys() {
+        check_compiles(
+            &mut compiler,
+            name,
+            &references,
+            &mut compiles,
+            &mut findings,
+        );
+    }
+
+    LintReport { findings }
+}
+
+/// Compiles the pattern after the patterns it refers to and returns whether it compiles.
+///
+/// A failure is only reported if all the patterns it refers to compile, so a broken
+/// pattern is not reported again for every pattern using it. Undefined references and
+/// cycles have their own findings and are not reported either.
+fn check_compiles<'a>(
+    grok: &mut Grok,
+    name: &'a str,
+    references: &BTreeMap<&'a str, BTreeSet<&'a str>>,
+    compiles: &mut BTreeMap<&'a str, bool>,
+    findings: &mut Vec<Finding>,
+) -> bool {
+    if let Some(&result) = compiles.get(name) {
+        return result;
+    }
+    let refers_to = match references.get(name) {
+        Some(r) => r,
+        None => return false,
+    };
+    // Assume a failure while the references are checked, which ends cycles.
+    compiles.insert(name, false);
+
+    let mut result = true;
+    for reference in refers_to {
+        result &= check_compiles(grok, reference, references, compiles, findings);
+    }
+    if result {
+        match grok.compile(&format!("%{{{}}}", name), false) {
+            Ok(_) => {}
+            Err(Error::DefinitionsNotFound(_)) | Err(Error::ReferenceCycle(_)) => result = false,
+            Err(error) => {
+                findings.push(Finding::CompilationFailed {
+                    pattern: name.into(),
+                    error,
+                });
+                result = false;
+            }
+        }
+    }
+    compiles.insert(name, result);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint() {
+        let mut grok = Grok::empty();
+        grok.add_pattern("WORD", r"\w+");
+        grok.add_pattern("WORD", r"\b\w+\b");
+        grok.add_pattern("GREETING", "hello %{WORD:name}");
+        grok.add_pattern("BROKEN", "(%{WORD}");
+        grok.add_pattern("USES_BROKEN", "%{BROKEN} %{GREETING}");
+        grok.add_pattern("TYPO", "%{WROD} %{NUM=\\d+}");
+        grok.add_pattern("PING", "%{PONG}");
+        grok.add_pattern("PONG", "%{PING}");
+
+        let report = grok.lint();
+        assert_eq!(
+            &[
+                Finding::UndefinedReference {
+                    pattern: "TYPO".into(),
+                    reference: "%{WROD}".into(),
+                    offset: 0,
+                    suggestions: vec!["WORD".into()],
+                },
+                Finding::Duplicate {
+                    pattern: "WORD".into(),
+                    sources: vec![Source::Custom, Source::Custom],
+                },
+                Finding::Cycle(vec!["PING".into(), "PONG".into(), "PING".into()]),
+                Finding::Unused("TYPO".into()),
+                Finding::Unused("USES_BROKEN".into()),
+            ],
+            &report.findings()[..5]
+        );
+        assert_eq!(6, report.findings().len());
+        assert!(matches!(
+            report.findings()[5],
+            Finding::CompilationFailed { ref pattern, .. } if pattern == "BROKEN"
+        ));
+        assert_eq!(4, report.problems().count());
+    }
+
+    #[test]
+    fn test_lint_sources() {
+        let mut grok = Grok::empty();
+        grok.add_patterns_from_reader("WORD \\w+\n".as_bytes())
+            .expect("Error while reading!");
+        grok.add_patterns_from_reader("WORD \\w+\n".as_bytes())
+            .expect("Error while reading!");
+        assert!(grok.lint().problems().next().is_none());
+
+        grok.add_pattern("WORD", r"\S+");
+        grok.compile(r"%{WORD=[a-z]+}", false)
+            .expect("Error while compiling!");
+        assert_eq!(
+            vec![&Finding::Duplicate {
+                pattern: "WORD".into(),
+                sources: vec![Source::Reader, Source::Custom, Source::Inline],
+            }],
+            grok.lint().problems().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "onig", feature = "default-patterns"))]
+    fn test_lint_default_patterns() {
+        let report = Grok::with_default_patterns_compat(Compat::Legacy).lint();
+        assert_eq!(
+            vec!["JAVACLASS", "JAVAFILE"],
+            report
+                .problems()
+                .map(|f| match *f {
+                    Finding::Duplicate { ref pattern, .. } => pattern.as_str(),
+                    ref f => panic!("Unexpected finding: {}", f),
+                })
+                .collect::<Vec<_>>()
+        );
+        let report = Grok::with_default_patterns_compat(Compat::EcsV1).lint();
+        assert!(report.problems().next().is_none());
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{Error, Value};
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+
+/// The matches as a tree, where field names like `[http][response][status_code]` or
+/// `http.response.status_code` are split into their path, see `Matches::to_nested`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Nested<'a> {
+    /// The (typed) value of a field.
+    Value(Value<'a>),
+    /// The fields below a path segment, by the name of their next segment.
+    Map(BTreeMap<String, Nested<'a>>),
+}
+
+impl<'a> Nested<'a> {
+    /// Returns the node at the given path, where each segment is one level of nesting.
+    pub fn get(&self, path: &[&str]) -> Option<&Nested<'a>> {
+        path.iter().try_fold(self, |node, segment| match *node {
+            Nested::Map(ref map) => map.get(*segment),
+            Nested::Value(_) => None,
+        })
+    }
+
+    /// Returns the value if this node is a leaf.
+    pub fn as_value(&self) -> Option<&Value<'a>> {
+        match *self {
+            Nested::Value(ref value) => Some(value),
+            Nested::Map(_) => None,
+        }
+    }
+
+    /// Returns the children if this node is a map.
+    pub fn as_map(&self) -> Option<&BTreeMap<String, Nested<'a>>> {
+        match *self {
+            Nested::Map(ref map) => Some(map),
+            Nested::Value(_) => None,
+        }
+    }
+}
+
+/// Splits a field name into the segments of its path.
+///
+/// Bracketed names like `[source][address]` and dotted names like `source.address` are
+/// split, every other name (including ones with empty segments like `a..b`) is a single
+/// segment.
+pub(crate) fn field_path(name: &str) -> Vec<&str> {
+    if let Some(inner) = name.strip_prefix('[').and_then(|n| n.strip_suffix(']')) {
+        return inner.split("][").collect();
+    }
+    let segments = name.split('.').collect::<Vec<_>>();
+    if segments.iter().any(|s| s.is_empty()) {
+        vec![name]
+    } else {
+        segments
+    }
+}
+
+/// Builds the tree from the fields, failing if a path is used by two fields or is both a
+/// value and a map.
+pub(crate) fn build<'a, I>(fields: I) -> Result<Nested<'a>, Error>
+where
+    I: IntoIterator<Item = (&'a str, Value<'a>)>,
+{
+    // The field which created each node, to name both sides of a conflict.
+    let mut owners: BTreeMap<Vec<&str>, &str> = BTreeMap::new();
+    let mut root = BTreeMap::new();
+    for (name, value) in fields {
+        let path = field_path(name);
+        let conflict = |end: usize| Error::NestedFieldConflict {
+            field: name.into(),
+            other: owners[&path[..end]].into(),
+        };
+
+        let (last, parents) = path.split_last().expect("a path has at least one segment");
+        let mut map = &mut root;
+        for (depth, segment) in parents.iter().enumerate() {
+            let node = map
+                .entry((*segment).to_string())
+                .or_insert_with(|| Nested::Map(BTreeMap::new()));
+            map = match *node {
+                Nested::Map(ref mut children) => children,
+                Nested::Value(_) => return Err(conflict(depth + 1)),
+            };
+        }
+        match map.entry((*last).to_string()) {
+            Entry::Vacant(entry) => {
+                entry.insert(Nested::Value(value));
+            }
+            Entry::Occupied(_) => return Err(conflict(path.len())),
+        }
+        for end in 1..=path.len() {
+            owners.entry(path[..end].to_vec()).or_insert(name);
+        }
+    }
+    Ok(Nested::Map(root))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_path() {
+        assert_eq!(vec!["verb"], field_path("verb"));
+        assert_eq!(vec!["source", "address"], field_path("[source][address]"));
+        assert_eq!(
+            vec!["http", "response", "code"],
+            field_path("http.response.code")
+        );
+        assert_eq!(vec!["a..b"], field_path("a..b"));
+        assert_eq!(vec![".a"], field_path(".a"));
+    }
+
+    #[test]
+    fn test_build_conflicts() {
+        let conflict = build(vec![
+            ("[http][response]", Value::Str("ok")),
+            ("http.response.code", Value::Int(200)),
+        ]);
+        assert_eq!(
+            Err(Error::NestedFieldConflict {
+                field: "http.response.code".into(),
+                other: "[http][response]".into(),
+            }),
+            conflict
+        );
+
+        let conflict = build(vec![
+            ("[http][response][code]", Value::Int(200)),
+            ("http.response", Value::Str("ok")),
+        ]);
+        assert_eq!(
+            Err(Error::NestedFieldConflict {
+                field: "http.response".into(),
+                other: "[http][response][code]".into(),
+            }),
+            conflict
+        );
+
+        let conflict = build(vec![
+            ("[source][ip]", Value::Str("::1")),
+            ("source.ip", Value::Str("127.0.0.1")),
+        ]);
+        assert_eq!(
+            Err(Error::NestedFieldConflict {
+                field: "source.ip".into(),
+                other: "[source][ip]".into(),
+            }),
+            conflict
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use crate::engine::Flags;
+use std::collections::BTreeSet;
+
+/// The options to compile a pattern with, see `Grok::compile_with`.
+///
+/// The default options are the ones `Grok::compile` uses with `with_alias_only` set to
+/// false:
+///
+/// ```rs
+/// let mut grok = Grok::default();
+/// let options = CompileOptions::new()
+///     .alias_only(true)
+///     .anchored(true)
+///     .case_insensitive(true);
+/// let pattern = grok
+///     .compile_with("%{WORD:verb} %{URIPATH:path}", &options)
+///     .expect("Error while compiling!");
+/// assert!(pattern.match_against("get /index.html").is_some());
+/// assert!(pattern.match_against("> GET /index.html").is_none());
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CompileOptions {
+    pub(crate) alias_only: bool,
+    pub(crate) anchored: bool,
+    pub(crate) flags: Flags,
+    pub(crate) keep_empty_captures: bool,
+    pub(crate) fields: Option<BTreeSet<String>>,
+    pub(crate) convert_types: bool,
+    pub(crate) collect_repeated: bool,
+}
+
+impl CompileOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        CompileOptions {
+            alias_only: false,
+            anchored: false,
+            flags: Flags::default(),
+            keep_empty_captures: true,
+            fields: None,
+            convert_types: true,
+            collect_repeated: false,
+        }
+    }
+
+    /// Only captures the references which have an alias, like `%{WORD:verb}`, and not the
+    /// ones like `%{WORD}` (default: false).
+    pub fn alias_only(mut self, yes: bool) -> Self {
+        self.alias_only = yes;
+        self
+    }
+
+    /// Anchors the pattern with `\A...\z`, so it has to match the whole text, even if the
+    /// text spans several lines (default: false).
+    pub fn anchored(mut self, yes: bool) -> Self {
+        self.anchored = yes;
+        self
+    }
+
+    /// Matches letters regardless of their case (default: false).
+    pub fn case_insensitive(mut self, yes: bool) -> Self {
+        self.flags.case_insensitive = yes;
+        self
+    }
+
+    /// Ignores whitespace in the regex and allows `#` comments, like the `x` flag of most
+    /// regex engines (default: false).
+    pub fn ignore_whitespace(mut self, yes: bool) -> Self {
+        self.flags.ignore_whitespace = yes;
+        self
+    }
+
+    /// Lets `.` match a newline as well (default: false).
+    pub fn dot_matches_new_line(mut self, yes: bool) -> Self {
+        self.flags.dot_matches_new_line = yes;
+        self
+    }
+
+    /// Keeps fields which matched empty text (default: true).
+    ///
+    /// If disabled, a field which matched empty text is treated like a field which did
+    /// not participate in the match, which is what Logstash does by default.
+    pub fn keep_empty_captures(mut self, yes: bool) -> Self {
+        self.keep_empty_captures = yes;
+        self
+    }
+
+    /// Only captures the given fields, all other references are matched without being
+    /// captured (default: all fields).
+    pub fn fields<I, S>(mut self, fields: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.fields = Some(fields.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Applies the type suffixes of aliases like `%{INT:bytes:int}` (default: true).
+    ///
+    /// If disabled, the suffixes are ignored and all values are returned as `Value::Str`,
+    /// so also unknown converters do not fail the compilation.
+    pub fn convert_types(mut self, yes: bool) -> Self {
+        self.convert_types = yes;
+        self
+    }
+
+    /// Serializes fields which are captured more than once, like `WORD` in
+    /// `%{WORD} %{WORD}` or `hop` in a repeated `%{IP:hop}`, as arrays of all their values
+    /// in the order of the pattern (default: false).
+    ///
+    /// Such a field is always an array, even if only one of its groups participated in
+    /// the match. `Matches::get_all` returns the same values regardless of this option.
+    pub fn collect_repeated(mut self, yes: bool) -> Self {
+        self.collect_repeated = yes;
+        self
+    }
+
+    /// Returns true if the field is captured, according to the allow-list of fields.
+    pub(crate) fn captures(&self, field: &str) -> bool {
+        self.fields.as_ref().map_or(true, |f| f.contains(field))
+    }
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        CompileOptions::new()
+    }
+}
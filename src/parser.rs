@@ -0,0 +1,309 @@
+//! The parser which turns a grok expression into its references and the regex between them.
+//!
+//! A reference has the form `%{NAME}`, `%{NAME:alias}` or `%{NAME:alias:type}`, and may
+//! carry an inline definition as in `%{NAME:alias=regex}`. Text which looks like the start
+//! of a reference but does not follow this form is kept as regex, just like any other text.
+
+use std::ops::Range;
+
+/// A part of a parsed grok expression.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Node<'a> {
+    /// Regex which is used as-is.
+    Regex(&'a str),
+    /// A reference to a pattern.
+    Reference(Reference<'a>),
+}
+
+/// A reference to a pattern, like `%{IP:client:ip}`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Reference<'a> {
+    /// The byte offset of the reference in the expression.
+    pub(crate) offset: usize,
+    /// The whole reference as written in the expression.
+    pub(crate) text: &'a str,
+    /// The name of the referenced pattern.
+    pub(crate) name: &'a str,
+    /// The name of the field, if an alias is given.
+    pub(crate) alias: Option<&'a str>,
+    /// The name of the converter given after the alias, if any.
+    pub(crate) conversion: Option<&'a str>,
+    /// The inline definition of the pattern, if any.
+    pub(crate) definition: Option<&'a str>,
+}
+
+/// Parses the expression into its nodes, in order.
+pub(crate) fn parse(expression: &str) -> Vec<Node<'_>> {
+    let mut nodes = Vec::new();
+    let mut regex_start = 0;
+    let mut pos = 0;
+    while let Some(found) = expression[pos..].find("%{") {
+        let start = pos + found;
+        match parse_reference(expression, start) {
+            Some(reference) => {
+                if regex_start < start {
+                    nodes.push(Node::Regex(&expression[regex_start..start]));
+                }
+                pos = start + reference.text.len();
+                regex_start = pos;
+                nodes.push(Node::Reference(reference));
+            }
+            None => pos = start + 1,
+        }
+    }
+    if regex_start < expression.len() {
+        nodes.push(Node::Regex(&expression[regex_start..]));
+    }
+    nodes
+}
+
+/// Parses the reference starting with the `%{` at `start`, if it is a valid one.
+fn parse_reference(expression: &str, start: usize) -> Option<Reference<'_>> {
+    let bytes = expression.as_bytes();
+    let take = |from: usize, accept: fn(u8) -> bool| {
+        from + bytes[from..].iter().take_while(|&&b| accept(b)).count()
+    };
+
+    let name_start = start + 2;
+    let name_end = take(name_start, |b| b.is_ascii_alphanumeric() || b == b'_');
+    if name_end == name_start {
+        return None;
+    }
+    let mut pos = name_end;
+
+    let mut alias = None;
+    if bytes.get(pos) == Some(&b':') {
+        let end = take(pos + 1, is_alias_byte);
+        if end == pos + 1 {
+            return None;
+        }
+        alias = Some(&expression[pos + 1..end]);
+        pos = end;
+    }
+
+    let mut definition = None;
+    if bytes.get(pos) == Some(&b'=') {
+        let end = take(pos + 1, |b| b != b'{' && b != b'}');
+        if end == pos + 1 {
+            return None;
+        }
+        definition = Some(&expression[pos + 1..end]);
+        pos = end;
+    }
+
+    if bytes.get(pos) != Some(&b'}') {
+        return None;
+    }
+    let (alias, conversion) = match alias.map(|a| a.split_once(':')) {
+        Some(Some((alias, conversion))) => (Some(alias), Some(conversion)),
+        Some(None) => (alias, None),
+        None => (None, None),
+    };
+    Some(Reference {
+        offset: start,
+        text: &expression[start..=pos],
+        name: &expression[name_start..name_end],
+        alias,
+        conversion,
+        definition,
+    })
+}
+
+/// A group name in regex text, see `group_names`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum GroupName<'a> {
+    /// The name of a named capture group, like `(?<name>...)`, `(?'name'...)` or
+    /// `(?P<name>...)`.
+    Group(&'a str),
+    /// The name in a backreference or a subroutine call to a named group, like
+    /// `\k<name>`, `\k'name'`, `\g<name>`, `\g'name'` or `(?P=name)`.
+    Backreference(&'a str),
+}
+
+/// Finds the names of the named capture groups and of the backreferences to them in the
+/// regex text, together with the byte range of each name.
+///
+/// Escaped characters and character classes are skipped, so `\(?<a>` and `[(?<a>]` do
+/// not contain a group. Lookbehinds like `(?<=a)` and `(?<!a)` are not groups either.
+pub(crate) fn group_names(regex: &str) -> Vec<(Range<usize>, GroupName<'_>)> {
+    let bytes = regex.as_bytes();
+    let name_at = |start: usize, end: u8| {
+        let len = bytes[start..]
+            .iter()
+            .take_while(|&&b| b.is_ascii_alphanumeric() || b == b'_')
+            .count();
+        let valid = len > 0 && !bytes[start].is_ascii_digit();
+        (valid && bytes.get(start + len) == Some(&end)).then(|| start..start + len)
+    };
+
+    let mut names = Vec::new();
+    let mut in_class = false;
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let rest = &bytes[pos..];
+        if bytes[pos] == b'\\' {
+            let end = match rest.get(1..3) {
+                Some(b"k<") | Some(b"g<") => Some(b'>'),
+                Some(b"k'") | Some(b"g'") => Some(b'\''),
+                _ => None,
+            };
+            if let Some(range) = end
+                .filter(|_| !in_class)
+                .and_then(|end| name_at(pos + 3, end))
+            {
+                names.push((range.clone(), GroupName::Backreference(&regex[range])));
+            }
+            pos += 2;
+            continue;
+        }
+        match bytes[pos] {
+            b'[' if !in_class => in_class = true,
+            b']' if in_class => in_class = false,
+            b'(' if !in_class => {
+                let group = if rest.starts_with(b"(?<") {
+                    name_at(pos + 3, b'>').map(|r| (r.clone(), GroupName::Group(&regex[r])))
+                } else if rest.starts_with(b"(?'") {
+                    name_at(pos + 3, b'\'').map(|r| (r.clone(), GroupName::Group(&regex[r])))
+                } else if rest.starts_with(b"(?P<") {
+                    name_at(pos + 4, b'>').map(|r| (r.clone(), GroupName::Group(&regex[r])))
+                } else if rest.starts_with(b"(?P=") {
+                    name_at(pos + 4, b')').map(|r| (r.clone(), GroupName::Backreference(&regex[r])))
+                } else {
+                    None
+                };
+                names.extend(group);
+            }
+            _ => {}
+        }
+        pos += 1;
+    }
+    names
+}
+
+/// Returns true if every match of the regex starts at the start of a line, because it
+/// begins with `^` and has no alternation outside of groups.
+pub(crate) fn is_line_anchored(regex: &str) -> bool {
+    let bytes = regex.as_bytes();
+    if bytes.first() != Some(&b'^') {
+        return false;
+    }
+    let mut depth = 0usize;
+    let mut in_class = false;
+    let mut pos = 1;
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'\\' => pos += 1,
+            b'[' if !in_class => in_class = true,
+            b']' if in_class => in_class = false,
+            b'(' if !in_class => depth += 1,
+            b')' if !in_class => depth = depth.saturating_sub(1),
+            b'|' if !in_class && depth == 0 => return false,
+            _ => {}
+        }
+        pos += 1;
+    }
+    true
+}
+
+/// Returns true if the byte may be part of an alias, including its type suffix.
+fn is_alias_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric()
+        || b.is_ascii_whitespace()
+        || matches!(
+            b,
+            b'_' | b':' | b';' | b'/' | b'.' | b'-' | b'@' | b'[' | b']'
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reference<'a>(offset: usize, text: &'a str, name: &'a str) -> Reference<'a> {
+        Reference {
+            offset,
+            text,
+            name,
+            alias: None,
+            conversion: None,
+            definition: None,
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            vec![
+                Node::Regex("^"),
+                Node::Reference(reference(1, "%{WORD}", "WORD")),
+                Node::Regex(r"\s+"),
+                Node::Reference(Reference {
+                    alias: Some("[http][status]"),
+                    conversion: Some("int"),
+                    ..reference(11, "%{INT:[http][status]:int}", "INT")
+                }),
+                Node::Reference(Reference {
+                    alias: Some("rest"),
+                    definition: Some(".*"),
+                    ..reference(36, "%{REST:rest=.*}", "REST")
+                }),
+            ],
+            parse(r"^%{WORD}\s+%{INT:[http][status]:int}%{REST:rest=.*}")
+        );
+        assert_eq!(
+            vec![Node::Reference(Reference {
+                definition: Some(r"\d+"),
+                ..reference(0, r"%{NUM=\d+}", "NUM")
+            })],
+            parse(r"%{NUM=\d+}")
+        );
+        assert_eq!(Vec::<Node>::new(), parse(""));
+    }
+
+    #[test]
+    fn test_group_names() {
+        let regex = r"(?<first>\w+) (?P<second>\d+)(?<=x)(?<!y) \k<first>(?P=second)";
+        assert_eq!(
+            vec![
+                (3..8, GroupName::Group("first")),
+                (18..24, GroupName::Group("second")),
+                (45..50, GroupName::Backreference("first")),
+                (55..61, GroupName::Backreference("second")),
+            ],
+            group_names(regex)
+        );
+        for regex in [r"\(?<a>b)", r"[(?<a>]", r"(?<1a>b)", r"(?<a", r"\\k<>"] {
+            assert_eq!(Vec::<(Range<usize>, GroupName)>::new(), group_names(regex));
+        }
+        assert_eq!(
+            vec![(11..12, GroupName::Group("a"))],
+            group_names(r"\é[\]x](?<a>b)")
+        );
+        assert_eq!(
+            vec![
+                (3..4, GroupName::Group("a")),
+                (10..11, GroupName::Backreference("a")),
+                (15..16, GroupName::Backreference("a")),
+                (20..21, GroupName::Backreference("a")),
+            ],
+            group_names(r"(?'a'b)\k'a'\g<a>\g'a'[\g<b>]\g<1>")
+        );
+    }
+
+    #[test]
+    fn test_parse_keeps_invalid_references_as_regex() {
+        for expression in [
+            "%{", "%{}", "%{WORD", "%{WORD:}", "%{WORD=}", "%{WO RD}", "100%",
+        ] {
+            assert_eq!(vec![Node::Regex(expression)], parse(expression));
+        }
+        assert_eq!(
+            vec![
+                Node::Regex("%{"),
+                Node::Reference(reference(2, "%{WORD}", "WORD")),
+                Node::Regex("}"),
+            ],
+            parse("%{%{WORD}}")
+        );
+    }
+}
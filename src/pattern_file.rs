@@ -0,0 +1,63 @@
+//! The format of pattern files, shared between the library and `build.rs`.
+//!
+//! Every line holds the name of a pattern and its definition, separated by the first
+//! space. Empty lines and lines starting with `#` are ignored.
+
+/// Parses a single line of a pattern file into the name and the definition of the pattern.
+///
+/// Returns `None` for comments and blank lines, or the reason if the line is malformed.
+pub(crate) fn parse_line(line: &str) -> Result<Option<(&str, &str)>, String> {
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    if line.starts_with('#') || line.trim().is_empty() {
+        return Ok(None);
+    }
+
+    let (name, definition) = match line.split_once(' ') {
+        Some(parts) => parts,
+        None => return Err(format!("missing definition for the pattern \"{}\"", line)),
+    };
+    if name.is_empty() {
+        return Err("the line starts with a space instead of a pattern name".into());
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| !c.is_ascii_alphanumeric() && *c != '_')
+    {
+        return Err(format!(
+            "invalid character '{}' in the pattern name \"{}\"",
+            c, name
+        ));
+    }
+    if definition.trim().is_empty() {
+        return Err(format!("missing definition for the pattern \"{}\"", name));
+    }
+    Ok(Some((name, definition)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        assert_eq!(Ok(None), parse_line(""));
+        assert_eq!(Ok(None), parse_line("  \r"));
+        assert_eq!(Ok(None), parse_line("# USERNAME [a-z]+"));
+        assert_eq!(
+            Ok(Some(("USERNAME", "[a-zA-Z0-9._-]+"))),
+            parse_line("USERNAME [a-zA-Z0-9._-]+")
+        );
+        assert_eq!(
+            Ok(Some(("SPACED", "%{WORD} %{WORD}"))),
+            parse_line("SPACED %{WORD} %{WORD}\r")
+        );
+    }
+
+    #[test]
+    fn test_parse_malformed_line() {
+        assert!(parse_line("USERNAME").is_err());
+        assert!(parse_line("USERNAME   ").is_err());
+        assert!(parse_line(" USERNAME [a-z]+").is_err());
+        assert!(parse_line("USER-NAME [a-z]+").is_err());
+    }
+}
@@ -0,0 +1,426 @@
+use memchr::memmem::Finder;
+
+/// A `Prefilter` holds literal substrings which every match of a regex has to contain.
+///
+/// Checking for them is a lot cheaper than running the regex engine, so a text which
+/// lacks one of them can be rejected right away. The literals are extracted
+/// conservatively: whenever the regex uses a construct whose meaning is not obvious
+/// (like inline options or unknown escapes), no prefilter is built at all.
+#[derive(Debug)]
+pub(crate) struct Prefilter {
+    finders: Vec<Finder<'static>>,
+}
+
+impl Prefilter {
+    /// Builds the prefilter for the given regex, or returns `None` if no required literal
+    /// could be found.
+    pub(crate) fn new(regex: &str) -> Option<Self> {
+        let mut literals = required_literals(regex)?;
+        // Single bytes are kept, they are the fastest to search for.
+        literals.retain(|l| !l.is_empty());
+        literals.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        literals.dedup();
+        if literals.is_empty() {
+            return None;
+        }
+        Some(Prefilter {
+            finders: literals
+                .iter()
+                .map(|l| Finder::new(l.as_bytes()).into_owned())
+                .collect(),
+        })
+    }
+
+    /// Returns false if the text cannot possibly match the regex.
+    pub(crate) fn may_match(&self, text: &str) -> bool {
+        self.finders
+            .iter()
+            .all(|f| f.find(text.as_bytes()).is_some())
+    }
+}
+
+/// Returns the literal substrings every match of the regex contains, or `None` if the
+/// regex cannot be analyzed.
+pub(crate) fn required_literals(regex: &str) -> Option<Vec<String>> {
+    let mut parser = Parser {
+        chars: regex.chars().collect(),
+        pos: 0,
+    };
+    let literals = parser.sequence()?;
+    if parser.pos != parser.chars.len() {
+        // An unbalanced closing parenthesis.
+        return None;
+    }
+    Some(literals)
+}
+
+/// A single element of a sequence, before an eventual quantifier is applied.
+enum Atom {
+    /// A literal character.
+    Char(char),
+    /// A group with the literals it requires.
+    Group(Vec<String>),
+    /// Anything else, which breaks a run of literal characters.
+    Other,
+}
+
+/// A small parser for the Oniguruma (Ruby) regex syntax, just detailed enough to find
+/// out which literals are required.
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    /// Parses a sequence up to the closing parenthesis of the current group (or the end)
+    /// and returns the literals it requires.
+    fn sequence(&mut self) -> Option<Vec<String>> {
+        let mut literals = Vec::new();
+        let mut run = String::new();
+        let mut alternation = false;
+
+        while let Some(c) = self.peek() {
+            let atom = match c {
+                ')' => break,
+                '|' => {
+                    self.pos += 1;
+                    alternation = true;
+                    flush(&mut run, &mut literals);
+                    continue;
+                }
+                '(' => Atom::Group(self.group()?),
+                '[' => {
+                    self.class()?;
+                    Atom::Other
+                }
+                '\\' => self.escape()?,
+                '.' | '^' | '$' => {
+                    self.pos += 1;
+                    Atom::Other
+                }
+                '*' | '+' | '?' => return None,
+                '{' if self.interval().is_some() => return None,
+                c => {
+                    self.pos += 1;
+                    Atom::Char(c)
+                }
+            };
+
+            match (atom, self.quantifier()) {
+                (Atom::Char(c), None) => run.push(c),
+                (Atom::Char(c), Some(false)) => {
+                    run.push(c);
+                    flush(&mut run, &mut literals);
+                }
+                (Atom::Group(group), None) | (Atom::Group(group), Some(false)) => {
+                    flush(&mut run, &mut literals);
+                    literals.extend(group);
+                }
+                _ => flush(&mut run, &mut literals),
+            }
+        }
+        flush(&mut run, &mut literals);
+
+        // Only literals which are required by every branch would be valid, keep it simple.
+        if alternation {
+            literals.clear();
+        }
+        Some(literals)
+    }
+
+    /// Parses a group starting at the opening parenthesis.
+    fn group(&mut self) -> Option<Vec<String>> {
+        self.pos += 1;
+        let required = if self.peek() == Some('?') {
+            match (self.peek_at(1), self.peek_at(2)) {
+                (Some(':'), _) | (Some('>'), _) => {
+                    self.pos += 2;
+                    true
+                }
+                (Some('='), _) | (Some('!'), _) => {
+                    self.pos += 2;
+                    false
+                }
+                (Some('<'), Some('=')) | (Some('<'), Some('!')) => {
+                    self.pos += 3;
+                    false
+                }
+                (Some('<'), _) => {
+                    self.pos += 2;
+                    self.skip_past('>')?;
+                    true
+                }
+                (Some('\''), _) => {
+                    self.pos += 2;
+                    self.skip_past('\'')?;
+                    true
+                }
+                (Some('P'), Some('<')) => {
+                    self.pos += 3;
+                    self.skip_past('>')?;
+                    true
+                }
+                (Some('#'), _) => {
+                    self.skip_past(')')?;
+                    return Some(Vec::new());
+                }
+                // Inline options, conditionals and everything else we do not understand.
+                _ => return None,
+            }
+        } else {
+            true
+        };
+
+        let literals = self.sequence()?;
+        if self.peek() != Some(')') {
+            return None;
+        }
+        self.pos += 1;
+        Some(if required { literals } else { Vec::new() })
+    }
+
+    /// Skips a character class starting at the opening bracket, including nested classes.
+    fn class(&mut self) -> Option<()> {
+        self.pos += 1;
+        if self.peek() == Some('^') {
+            self.pos += 1;
+        }
+        if self.peek() == Some(']') {
+            self.pos += 1;
+        }
+        let mut depth = 1;
+        while depth > 0 {
+            match self.peek()? {
+                '\\' => self.pos += 1,
+                '[' => depth += 1,
+                ']' => depth -= 1,
+                _ => {}
+            }
+            self.pos += 1;
+        }
+        Some(())
+    }
+
+    /// Parses an escape sequence starting at the backslash.
+    fn escape(&mut self) -> Option<Atom> {
+        let c = self.peek_at(1)?;
+        self.pos += 2;
+        let atom = match c {
+            c if !c.is_ascii_alphanumeric() => Atom::Char(c),
+            't' => Atom::Char('\t'),
+            'n' => Atom::Char('\n'),
+            'r' => Atom::Char('\r'),
+            'f' => Atom::Char('\x0C'),
+            'v' => Atom::Char('\x0B'),
+            'a' => Atom::Char('\x07'),
+            'e' => Atom::Char('\x1B'),
+            'w' | 'W' | 's' | 'S' | 'd' | 'D' | 'h' | 'H' | 'b' | 'B' | 'A' | 'z' | 'Z' | 'G'
+            | 'K' | 'R' | 'N' | 'O' | 'X' | 'y' | 'Y' => Atom::Other,
+            'x' => {
+                if self.peek() == Some('{') {
+                    self.skip_past('}')?;
+                } else {
+                    self.skip_while(2, |c| c.is_ascii_hexdigit());
+                }
+                Atom::Other
+            }
+            'u' => {
+                self.skip_while(4, |c| c.is_ascii_hexdigit());
+                Atom::Other
+            }
+            '0'..='9' => {
+                self.skip_while(3, |c| c.is_ascii_digit());
+                Atom::Other
+            }
+            'k' | 'g' => {
+                match self.peek()? {
+                    '<' => self.skip_past('>')?,
+                    '\'' => {
+                        self.pos += 1;
+                        self.skip_past('\'')?
+                    }
+                    _ => return None,
+                }
+                Atom::Other
+            }
+            'p' | 'P' => {
+                if self.peek() != Some('{') {
+                    return None;
+                }
+                self.skip_past('}')?;
+                Atom::Other
+            }
+            _ => return None,
+        };
+        Some(atom)
+    }
+
+    /// Parses an eventual quantifier and returns whether it allows zero repetitions.
+    fn quantifier(&mut self) -> Option<bool> {
+        let allows_zero = match self.peek() {
+            Some('*') | Some('?') => {
+                self.pos += 1;
+                true
+            }
+            Some('+') => {
+                self.pos += 1;
+                false
+            }
+            Some('{') => {
+                let (len, min) = self.interval()?;
+                self.pos += len;
+                min == 0
+            }
+            _ => return None,
+        };
+        // Lazy and possessive modifiers do not change what is required.
+        if let Some('?') | Some('+') = self.peek() {
+            self.pos += 1;
+        }
+        Some(allows_zero)
+    }
+
+    /// Checks if an interval quantifier like `{2,5}` starts at the current position and
+    /// returns its length and the minimum number of repetitions.
+    fn interval(&self) -> Option<(usize, usize)> {
+        let mut len = 1;
+        let mut min = String::new();
+        let mut digits = 0;
+        while let Some(c) = self.peek_at(len).filter(|c| c.is_ascii_digit()) {
+            min.push(c);
+            len += 1;
+            digits += 1;
+        }
+        if self.peek_at(len) == Some(',') {
+            len += 1;
+            while self.peek_at(len).filter(|c| c.is_ascii_digit()).is_some() {
+                len += 1;
+                digits += 1;
+            }
+        }
+        if digits == 0 || self.peek_at(len) != Some('}') {
+            return None;
+        }
+        Some((len + 1, min.parse().unwrap_or(0)))
+    }
+
+    /// Moves past the next occurrence of the given character.
+    fn skip_past(&mut self, end: char) -> Option<()> {
+        while self.peek()? != end {
+            self.pos += 1;
+        }
+        self.pos += 1;
+        Some(())
+    }
+
+    /// Moves past at most `max` characters matching the predicate.
+    fn skip_while<F: Fn(char) -> bool>(&mut self, max: usize, predicate: F) {
+        let mut skipped = 0;
+        while skipped < max && self.peek().map_or(false, &predicate) {
+            self.pos += 1;
+            skipped += 1;
+        }
+    }
+}
+
+/// Finishes the current run of literal characters.
+fn flush(run: &mut String, literals: &mut Vec<String>) {
+    if !run.is_empty() {
+        literals.push(std::mem::take(run));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_required_literals() {
+        assert_eq!(
+            Some(vec!["hello".into(), "world".into()]),
+            required_literals(r"hello\s+world")
+        );
+        assert_eq!(
+            Some(vec![" [".into(), "] ".into()]),
+            required_literals(r"(?<grok0>\d+) \[(?<grok1>[^\]]+)\] (?<grok2>.*)")
+        );
+        assert_eq!(
+            Some(vec!["ab".into(), "d".into()]),
+            required_literals(r"abc?d")
+        );
+        assert_eq!(
+            Some(vec!["abc".into(), "d".into()]),
+            required_literals(r"abc+d")
+        );
+        assert_eq!(Some(vec!["ab".into()]), required_literals(r"ab(?:cd)?"));
+        assert_eq!(
+            Some(vec!["cd".into()]),
+            required_literals(r"(?:cd){1,3}x{0,2}")
+        );
+        assert_eq!(Some(vec![]), required_literals(r"foo|bar"));
+        assert_eq!(Some(vec!["ab".into()]), required_literals(r"ab(?:foo|bar)"));
+        assert_eq!(Some(vec!["x]y".into()]), required_literals(r"x\]y[]a-z]"));
+        assert_eq!(
+            Some(vec!["ab".into()]),
+            required_literals(r"ab(?=cd)(?<!ef)")
+        );
+        assert_eq!(
+            Some(vec!["GET".into()]),
+            required_literals(r"\x41GET\p{Alpha}\k<grok0>")
+        );
+        assert_eq!(
+            Some(vec!["%{A:b-c}".into()]),
+            required_literals(r"%{A:b-c}")
+        );
+    }
+
+    #[test]
+    fn test_unsupported_constructs() {
+        assert_eq!(None, required_literals(r"(?i)hello"));
+        assert_eq!(None, required_literals(r"(?i:hello)"));
+        assert_eq!(None, required_literals(r"(?(1)a|b)"));
+        assert_eq!(None, required_literals(r"\Qhello\E"));
+        assert_eq!(None, required_literals(r"*hello"));
+        assert_eq!(None, required_literals(r"hello)"));
+        assert_eq!(None, required_literals(r"(hello"));
+    }
+
+    #[test]
+    fn test_prefilter() {
+        assert!(Prefilter::new(r"\d+").is_none());
+        assert!(Prefilter::new(r"a|b").is_none());
+        let prefilter = Prefilter::new(r"\d+ \[\w+\] done").expect("No prefilter built!");
+        assert!(prefilter.may_match("12 [x] done"));
+        assert!(!prefilter.may_match("12 [x] failed"));
+
+        let prefilter = Prefilter::new(r"\d+;\w+").expect("No prefilter built!");
+        assert!(prefilter.may_match("12;ab"));
+        assert!(!prefilter.may_match("12:ab"));
+    }
+
+    #[test]
+    #[cfg(feature = "default-patterns")]
+    fn test_prefilter_rejects_single_byte_literal() {
+        let mut grok = crate::Grok::with_default_patterns();
+        let expansion = grok
+            .expand(
+                r"%{TIMESTAMP_ISO8601:timestamp} \[%{IPV4:ip};%{WORD:environment}\] %{LOGLEVEL:log_level} %{GREEDYDATA:message}",
+                &crate::CompileOptions::new(),
+                0,
+            )
+            .expect("Error while expanding!");
+        let prefilter = Prefilter::new(&expansion.regex).expect("No prefilter built!");
+        assert!(!prefilter
+            .may_match("2016-09-19T18:19:00 [8.8.8.8:prd] DEBUG this is an example log message"));
+        assert!(prefilter
+            .may_match("2016-09-19T18:19:00 [8.8.8.8;prd] DEBUG this is an example log message"));
+    }
+}
@@ -0,0 +1,201 @@
+use crate::{typed_value, Matches, Nested, Value};
+use serde::ser::{Error as SerError, Serialize, SerializeMap, Serializer};
+
+/// Returns the name and value of all participating fields, in the order their captures
+/// appear in the pattern.
+pub(crate) fn ordered_fields<'a>(matches: &'a Matches<'a>) -> Vec<(&'a str, &'a str)> {
+    let mut fields = matches
+        .names
+        .iter()
+        .filter_map(|(name, indices)| {
+            matches
+                .participating(indices)
+                .map(|(idx, v)| (idx, name.as_str(), v))
+        })
+        .collect::<Vec<_>>();
+    fields.sort_by_key(|&(idx, _, _)| idx);
+    fields.into_iter().map(|(_, name, v)| (name, v)).collect()
+}
+
+/// Serializes the matches as a map from field name to (typed) value.
+///
+/// Fields are emitted in the order their captures appear in the pattern and fields
+/// which did not participate in the match are left out. A failed type conversion is
+/// reported as a serialization error.
+///
+/// If the pattern has been compiled with `CompileOptions::collect_repeated`, fields which
+/// are captured more than once are emitted as arrays of all their values.
+impl<'a> Serialize for Matches<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let fields = ordered_fields(self);
+        let mut map = serializer.serialize_map(Some(fields.len()))?;
+        for (name, value) in fields {
+            if self.collect_repeated && self.names[name].len() > 1 {
+                let values = self
+                    .get_all(name)
+                    .into_iter()
+                    .map(|v| typed_value(self.types, name, v))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(S::Error::custom)?;
+                map.serialize_entry(name, &values)?;
+            } else {
+                let value = typed_value(self.types, name, value).map_err(S::Error::custom)?;
+                map.serialize_entry(name, &value)?;
+            }
+        }
+        map.end()
+    }
+}
+
+impl<'a> Serialize for Value<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Value::Str(s) => serializer.serialize_str(s),
+            Value::Int(i) => serializer.serialize_i64(i),
+            Value::Float(f) => serializer.serialize_f64(f),
+            Value::Bool(b) => serializer.serialize_bool(b),
+            Value::Custom(ref c) => serializer.collect_str(c),
+        }
+    }
+}
+
+impl<'a> Serialize for Nested<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Nested::Value(ref value) => value.serialize(serializer),
+            Nested::Map(ref map) => serializer.collect_map(map),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CompileOptions, Grok};
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[test]
+    #[cfg(feature = "default-patterns")]
+    fn test_serialize_matches() {
+        let mut grok = Grok::default();
+        let pattern = grok
+            .compile("%{WORD:verb} %{URIPATH:path} %{INT:status:int}", true)
+            .expect("Error while compiling!");
+        let matches = pattern
+            .match_against("GET /index.html 200")
+            .expect("No matches found!");
+
+        let value = serde_json::to_value(&matches).expect("Serialization failed!");
+        assert_eq!(
+            json!({"verb": "GET", "path": "/index.html", "status": 200}),
+            value
+        );
+        assert_eq!(
+            r#"{"verb":"GET","path":"/index.html","status":200}"#,
+            serde_json::to_string(&matches).unwrap()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "default-patterns")]
+    fn test_serialize_conversion_failure() {
+        let mut grok = Grok::default();
+        let pattern = grok
+            .compile("%{WORD:status:int}", true)
+            .expect("Error while compiling!");
+        let matches = pattern.match_against("ok").expect("No matches found!");
+
+        assert!(serde_json::to_value(&matches).is_err());
+    }
+
+    #[test]
+    fn test_serialize_repeated() {
+        let mut grok = Grok::empty();
+        grok.add_pattern("WORD", r"\w+");
+        grok.add_pattern("INT", r"\d+");
+        let text = "traceroute 10 20 30";
+        let pattern = r"%{WORD} %{INT:hop:int} %{INT:hop:int} %{INT:hop:int}";
+
+        let options = CompileOptions::new().collect_repeated(true);
+        let collected = grok
+            .compile_with(pattern, &options)
+            .expect("Error while compiling!");
+        let matches = collected.match_against(text).expect("No matches found!");
+        assert_eq!(vec!["10", "20", "30"], matches.get_all("hop"));
+        assert_eq!(
+            json!({"WORD": "traceroute", "hop": [10, 20, 30]}),
+            serde_json::to_value(&matches).expect("Serialization failed!")
+        );
+
+        let single = grok
+            .compile_with(pattern, &CompileOptions::new())
+            .expect("Error while compiling!");
+        let matches = single.match_against(text).expect("No matches found!");
+        assert_eq!(vec!["10", "20", "30"], matches.get_all("hop"));
+        assert_eq!(
+            json!({"WORD": "traceroute", "hop": 30}),
+            serde_json::to_value(&matches).expect("Serialization failed!")
+        );
+    }
+
+    #[test]
+    fn test_repeated_round_trip() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Trace {
+            #[serde(rename = "WORD")]
+            word: String,
+            hop: Vec<u32>,
+            host: Option<Vec<String>>,
+        }
+
+        let mut grok = Grok::empty();
+        grok.add_pattern("WORD", r"\w+");
+        grok.add_pattern("INT", r"\d+");
+        let options = CompileOptions::new().collect_repeated(true);
+        let pattern = grok
+            .compile_with(
+                r"%{WORD} %{INT:hop:int} %{INT:hop:int} %{WORD:host} %{WORD:host}",
+                &options,
+            )
+            .expect("Error while compiling!");
+        let matches = pattern
+            .match_against("traceroute 10 20 gw router")
+            .expect("No matches found!");
+
+        let expected = Trace {
+            word: "traceroute".into(),
+            hop: vec![10, 20],
+            host: Some(vec!["gw".into(), "router".into()]),
+        };
+        assert_eq!(expected, matches.deserialize::<Trace>().unwrap());
+        let value = serde_json::to_value(&matches).expect("Serialization failed!");
+        assert_eq!(
+            expected,
+            serde_json::from_value::<Trace>(value).expect("Deserialization failed!")
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "default-patterns")]
+    fn test_serialize_nested() {
+        let mut grok = Grok::default();
+        let pattern = grok
+            .compile(
+                "%{WORD:[http][request][method]} %{URIPATH:url.path} %{INT:[http][response][status_code]:int}",
+                true,
+            )
+            .expect("Error while compiling!");
+        let matches = pattern
+            .match_against("GET /index.html 200")
+            .expect("No matches found!");
+
+        let nested = matches.to_nested().expect("Nesting failed!");
+        assert_eq!(
+            json!({
+                "http": {"request": {"method": "GET"}, "response": {"status_code": 200}},
+                "url": {"path": "/index.html"}
+            }),
+            serde_json::to_value(&nested).expect("Serialization failed!")
+        );
+    }
+}
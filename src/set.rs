@@ -0,0 +1,580 @@
+use crate::engine::{self, Engine, Flags, Regex};
+use crate::{parser, CompileOptions, Error, Grok, Matches, Pattern};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::slice::Iter as SliceIter;
+use std::sync::{Arc, RwLock};
+
+/// A `PatternSet` holds several compiled patterns which are tried in order.
+///
+/// This is the equivalent of passing a list of patterns to the logstash grok filter, which
+/// is handy for sources that write log lines in several formats.
+#[derive(Debug)]
+pub struct PatternSet {
+    patterns: Vec<Pattern>,
+    single_pass: Option<SinglePass>,
+}
+
+/// The expanded regexes of the patterns of a set, together with the merged regexes which
+/// have been compiled for the runs of patterns whose prefilters passed so far.
+#[derive(Debug)]
+struct SinglePass {
+    regexes: Vec<String>,
+    /// For each pattern starting with `^`, which can be merged with its neighbors, the
+    /// pattern anchored at the start of the text instead.
+    anchored: Vec<Option<Regex>>,
+    flags: Flags,
+    /// The merged regexes compiled so far.
+    merged: RwLock<HashMap<MergedKey, Arc<Merged>>>,
+}
+
+/// The positions of the patterns of a merged regex, and whether it is meant for text with
+/// several lines.
+type MergedKey = (Vec<usize>, bool);
+
+/// The maximum number of runs of patterns merged regexes are compiled and kept for.
+const MAX_MERGED: usize = 64;
+
+/// Some patterns of a set merged into one regex, with one alternation branch per pattern.
+#[derive(Debug)]
+struct Merged {
+    regex: Regex,
+    branches: Vec<Branch>,
+}
+
+/// The capture groups of a single pattern within the merged regex.
+#[derive(Debug)]
+struct Branch {
+    /// The position of the pattern in the set.
+    index: usize,
+    /// The index of the group wrapping the match of the pattern.
+    group: usize,
+    /// The groups of the pattern, in the same order as in the pattern itself. The range
+    /// of the last branch is open-ended.
+    groups: Range<usize>,
+}
+
+impl PatternSet {
+    /// Compiles all the given patterns with the `Grok` instance, identifying the position of
+    /// the pattern in the error if one of them fails. If `single_pass` is set, the patterns
+    /// are also prepared to be merged.
+    pub(crate) fn new<I, S>(
+        grok: &mut Grok,
+        patterns: I,
+        with_alias_only: bool,
+        single_pass: bool,
+    ) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        // The capture groups are numbered across all patterns so they stay unique once
+        // the patterns are merged.
+        let options = CompileOptions::new().alias_only(with_alias_only);
+        let mut next_index = 0;
+        let mut regexes = Vec::new();
+        let mut compiled = Vec::new();
+        for (index, pattern) in patterns.into_iter().enumerate() {
+            let (expansion, pattern) = grok
+                .expand(pattern.as_ref(), &options, next_index)
+                .and_then(|e| e.compile().map(|pattern| (e, pattern)))
+                .map_err(|e| Error::PatternSetCompilationFailed {
+                    index,
+                    source: Box::new(e),
+                })?;
+            next_index = expansion.next_index;
+            regexes.push(expansion.regex);
+            compiled.push(pattern);
+        }
+
+        // Without backtracking, the merged regex cannot prefer the first pattern in order.
+        let single_pass = if single_pass && Regex::BACKTRACKING {
+            let mut anchored = Vec::with_capacity(regexes.len());
+            for regex in &regexes {
+                anchored.push(if parser::is_line_anchored(regex) {
+                    Some(engine::compile(
+                        &format!(r"\A{}", &regex[1..]),
+                        options.flags,
+                    )?)
+                } else {
+                    None
+                });
+            }
+            let single_pass = SinglePass {
+                regexes,
+                anchored,
+                flags: options.flags,
+                merged: RwLock::new(HashMap::new()),
+            };
+            // All anchored patterns are merged up front, which also checks that they can be.
+            let all = (0..compiled.len())
+                .filter(|&index| single_pass.anchored[index].is_some())
+                .collect::<Vec<_>>();
+            if all.len() > 1 {
+                let merged = Merged::new(&single_pass.regexes, &all, options.flags, false)?;
+                if let Ok(mut subsets) = single_pass.merged.write() {
+                    subsets.insert((all, false), Arc::new(merged));
+                }
+            }
+            Some(single_pass)
+        } else {
+            None
+        };
+
+        Ok(PatternSet {
+            patterns: compiled,
+            single_pass,
+        })
+    }
+
+    /// Matches the patterns in order against the text and returns the first one that matched.
+    ///
+    /// If the set has been compiled in single pass mode, consecutive patterns which start
+    /// with `^` and whose prefilters do not rule out the text are matched at once with a
+    /// merged regex after the first of them, which returns the same pattern as trying them
+    /// one after another.
+    pub fn match_first<'a>(&'a self, text: &'a str) -> Option<SetMatch<'a>> {
+        let single_pass = match self.single_pass {
+            Some(ref single_pass) => single_pass,
+            None => return self.match_all(text).next(),
+        };
+        let multi_line = text.contains('\n');
+        // The first pattern of a run is tried on its own right away, as sets usually list
+        // the most frequent pattern first and merging only pays off once a pattern fails.
+        let mut first = true;
+        let mut run = Vec::new();
+        for (index, pattern) in self.patterns.iter().enumerate() {
+            if !pattern.may_match(text) {
+                continue;
+            }
+            if single_pass.anchored[index].is_some() {
+                if !first {
+                    run.push(index);
+                } else if let Some(found) =
+                    self.match_anchored(single_pass, index, text, multi_line)
+                {
+                    return Some(found);
+                }
+                first = false;
+                continue;
+            }
+            if let Some(found) = self.match_run(single_pass, &run, text, multi_line) {
+                return Some(found);
+            }
+            first = true;
+            run.clear();
+            if let Some(matches) = pattern.match_unfiltered(text) {
+                return Some(SetMatch { index, matches });
+            }
+        }
+        self.match_run(single_pass, &run, text, multi_line)
+    }
+
+    /// Matches the patterns at the given positions, which start with `^` and whose
+    /// prefilters passed already, with their merged regex.
+    fn match_run<'a>(
+        &'a self,
+        single_pass: &SinglePass,
+        run: &[usize],
+        text: &'a str,
+        multi_line: bool,
+    ) -> Option<SetMatch<'a>> {
+        let merged = match run.len() {
+            0 => return None,
+            1 => None,
+            _ => single_pass.merged(run, multi_line),
+        };
+        let merged = match merged {
+            Some(merged) => merged,
+            None => {
+                return run
+                    .iter()
+                    .find_map(|&index| self.match_anchored(single_pass, index, text, multi_line))
+            }
+        };
+
+        let locations = merged.regex.captures(text)?;
+        let branch = merged
+            .branches
+            .iter()
+            .find(|b| locations[b.group].is_some())?;
+        // The groups of the branch are numbered like the groups of the pattern itself.
+        let end = branch.groups.end.min(locations.len());
+        let mut own = vec![locations[branch.group]];
+        own.extend_from_slice(&locations[branch.groups.start..end]);
+        Some(SetMatch {
+            index: branch.index,
+            matches: self.patterns[branch.index].matches(text, own),
+        })
+    }
+
+    /// Matches the pattern at the given position, which starts with `^`, anchored at the
+    /// start of the text unless the text has several lines.
+    fn match_anchored<'a>(
+        &'a self,
+        single_pass: &SinglePass,
+        index: usize,
+        text: &'a str,
+        multi_line: bool,
+    ) -> Option<SetMatch<'a>> {
+        let pattern = &self.patterns[index];
+        let matches = match single_pass.anchored[index] {
+            Some(ref regex) if !multi_line => pattern.matches(text, regex.captures(text)?),
+            _ => pattern.match_unfiltered(text)?,
+        };
+        Some(SetMatch { index, matches })
+    }
+
+    /// Returns the matches of every pattern which matches the text, in order.
+    ///
+    /// The patterns are always tried one after another, even in single pass mode.
+    pub fn match_all<'a>(&'a self, text: &'a str) -> SetMatchesIter<'a> {
+        SetMatchesIter {
+            patterns: self.patterns.iter(),
+            index: 0,
+            text,
+        }
+    }
+
+    /// Returns the compiled pattern at the given index, if there is one.
+    pub fn get(&self, index: usize) -> Option<&Pattern> {
+        self.patterns.get(index)
+    }
+
+    /// Returns the number of patterns in this set.
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    /// Returns true if this set contains no patterns, false otherwise.
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Returns true if the patterns have been merged into a single regex.
+    pub fn is_single_pass(&self) -> bool {
+        self.single_pass.is_some()
+    }
+}
+
+impl SinglePass {
+    /// Returns the merged regex of the patterns at the given positions, compiling it on
+    /// first use. Returns `None` if too many runs have been merged already.
+    fn merged(&self, run: &[usize], multi_line: bool) -> Option<Arc<Merged>> {
+        let key = (run.to_vec(), multi_line);
+        if let Some(merged) = self.merged.read().ok()?.get(&key) {
+            return Some(merged.clone());
+        }
+        if self.merged.read().ok()?.len() >= MAX_MERGED {
+            return None;
+        }
+        // The patterns have been merged as a whole before, so a part of them merges too.
+        let merged = Merged::new(&self.regexes, run, self.flags, multi_line).ok()?;
+        let mut subsets = self.merged.write().ok()?;
+        Some(
+            subsets
+                .entry(key)
+                .or_insert_with(|| Arc::new(merged))
+                .clone(),
+        )
+    }
+}
+
+impl Merged {
+    /// Merges the regexes at the given positions, which all start with `^`, into a single
+    /// regex.
+    ///
+    /// Each branch is anchored at the start of the text, within an atomic group so the
+    /// engine cannot hand the alternation off to an automaton. For text with several
+    /// lines, a branch skips ahead line by line before the pattern itself. This way every
+    /// line is tried for one pattern before the next one, and the first pattern in order
+    /// wins wherever its match starts.
+    fn new(
+        regexes: &[String],
+        run: &[usize],
+        flags: Flags,
+        multi_line: bool,
+    ) -> Result<Self, Error> {
+        let skip = if multi_line {
+            r"(?:(?>[^\n]*)\n)*?"
+        } else {
+            ""
+        };
+        let regex = run
+            .iter()
+            .map(|&index| {
+                format!(
+                    r"\A(?>{}(?<{}{}>{}))",
+                    skip, BRANCH_PREFIX, index, regexes[index]
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("|");
+        let regex = engine::compile(&regex, flags)?;
+
+        let names = regex.capture_names();
+        let mut groups = HashMap::new();
+        for (name, idx) in &names {
+            if let Some(branch) = name.strip_prefix(BRANCH_PREFIX) {
+                if let Ok(branch) = branch.parse::<usize>() {
+                    groups.insert(branch, idx[0] as usize);
+                }
+            }
+        }
+
+        // The groups of a branch are the ones up to the group of the next branch, or all
+        // remaining ones for the last branch.
+        let branches = run
+            .iter()
+            .enumerate()
+            .map(|(position, &index)| {
+                let group = groups[&index];
+                let end = run
+                    .get(position + 1)
+                    .map_or(usize::MAX, |next| groups[next]);
+                Branch {
+                    index,
+                    group,
+                    groups: group + 1..end,
+                }
+            })
+            .collect();
+        Ok(Merged { regex, branches })
+    }
+}
+
+/// The prefix of the capture group names which mark the branch of each pattern.
+const BRANCH_PREFIX: &str = "grokbranch";
+
+/// The result of matching a `PatternSet`, which identifies the pattern that matched.
+#[derive(Debug)]
+pub struct SetMatch<'a> {
+    index: usize,
+    matches: Matches<'a>,
+}
+
+impl<'a> SetMatch<'a> {
+    /// Returns the index of the pattern that matched, in the order the set was compiled.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the matches of the pattern.
+    pub fn matches(&self) -> &Matches<'a> {
+        &self.matches
+    }
+
+    /// Consumes the set match and returns the matches of the pattern.
+    pub fn into_matches(self) -> Matches<'a> {
+        self.matches
+    }
+}
+
+/// An `Iterator` over all patterns of a `PatternSet` that match a text.
+pub struct SetMatchesIter<'a> {
+    patterns: SliceIter<'a, Pattern>,
+    index: usize,
+    text: &'a str,
+}
+
+impl<'a> Iterator for SetMatchesIter<'a> {
+    type Item = SetMatch<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for pattern in self.patterns.by_ref() {
+            let index = self.index;
+            self.index += 1;
+            if let Some(matches) = pattern.match_against(self.text) {
+                return Some(SetMatch { index, matches });
+            }
+        }
+        None
+    }
+}
+
+#[cfg(all(test, feature = "default-patterns"))]
+mod tests {
+    use crate::{Error, Grok};
+
+    #[test]
+    #[cfg(any(feature = "onig", feature = "fancy-regex"))]
+    fn test_match_first_and_all() {
+        let mut grok = Grok::default();
+        let set = grok
+            .compile_set(
+                [
+                    r"%{IPV4:ip} %{WORD:verb}",
+                    r"%{HOSTNAME:host} %{WORD:verb}",
+                    r"%{WORD:verb}",
+                ],
+                true,
+            )
+            .expect("Error while compiling!");
+        assert_eq!(3, set.len());
+
+        let found = set
+            .match_first("example.org GET")
+            .expect("No matches found!");
+        assert_eq!(1, found.index());
+        assert_eq!("example.org", found.matches().get("host").unwrap());
+
+        let all = set.match_all("127.0.0.1 GET").collect::<Vec<_>>();
+        assert_eq!(
+            vec![0, 1, 2],
+            all.iter().map(|m| m.index()).collect::<Vec<_>>()
+        );
+        assert_eq!("127.0.0.1", all[0].matches().get("ip").unwrap());
+
+        assert!(set.match_first("!!!").is_none());
+        assert_eq!(0, set.match_all("!!!").count());
+    }
+
+    #[test]
+    #[cfg(any(feature = "onig", feature = "fancy-regex"))]
+    fn test_single_pass() {
+        use crate::Value;
+
+        let mut grok = Grok::default();
+        let patterns = [
+            r"^%{IPV4:ip} %{WORD:verb} %{INT:status:int}$",
+            r"^%{HOSTNAME:host} %{WORD:verb}$",
+            r"^\[(?<thread>[^\]]+)\] %{GREEDYDATA:message}$",
+        ];
+        let set = grok
+            .compile_set_single_pass(patterns, true)
+            .expect("Error while compiling!");
+        let sequential = grok
+            .compile_set(patterns, true)
+            .expect("Error while compiling!");
+        assert!(set.is_single_pass());
+        assert!(!sequential.is_single_pass());
+
+        for line in [
+            "127.0.0.1 GET 200",
+            "example.org GET",
+            "[main] started",
+            "!!!",
+        ] {
+            let expected = sequential.match_first(line);
+            let actual = set.match_first(line);
+            assert_eq!(expected.is_some(), actual.is_some());
+            if let (Some(expected), Some(actual)) = (expected, actual) {
+                assert_eq!(expected.index(), actual.index());
+                assert_eq!(expected.matches().len(), actual.matches().len());
+                assert_eq!(
+                    expected.matches().iter().collect::<Vec<_>>(),
+                    actual.matches().iter().collect::<Vec<_>>()
+                );
+            }
+        }
+
+        let found = set.match_first("127.0.0.1 GET 200").unwrap();
+        assert_eq!(
+            Value::Int(200),
+            found.matches().get_typed("status").unwrap().unwrap()
+        );
+        assert_eq!(None, found.matches().get("host"));
+        let found = set.match_first("[main] started").unwrap();
+        assert_eq!("main", found.matches().get("thread").unwrap());
+    }
+
+    /// Checks that the set in single pass mode finds the same pattern and fields as trying
+    /// the patterns one after another.
+    fn assert_same_as_sequential(grok: &mut Grok, patterns: &[&str], lines: &[&str]) {
+        let set = grok
+            .compile_set_single_pass(patterns, true)
+            .expect("Error while compiling!");
+        let sequential = grok
+            .compile_set(patterns, true)
+            .expect("Error while compiling!");
+        for line in lines {
+            let expected = sequential.match_first(line);
+            let actual = set.match_first(line);
+            assert_eq!(expected.is_some(), actual.is_some(), "{:?}", line);
+            if let (Some(expected), Some(actual)) = (expected, actual) {
+                assert_eq!(expected.index(), actual.index(), "{:?}", line);
+                assert_eq!(
+                    expected.matches().match_span(),
+                    actual.matches().match_span()
+                );
+                assert_eq!(
+                    expected.matches().iter().collect::<Vec<_>>(),
+                    actual.matches().iter().collect::<Vec<_>>()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_single_pass_anchored() {
+        let mut grok = Grok::default();
+        let lines = [
+            "abc", "12", "abc; 12", "abc\n12", "12\nabc", "12; abc", "abc: 12", "x\n12; y", "!!!",
+        ];
+        // The prefilters leave a part of the patterns, which are merged after the first.
+        assert_same_as_sequential(
+            &mut grok,
+            &[r"^%{INT:n};", r"^%{WORD:w}:", r"^%{WORD:x}", r"^(?<y>\d+)"],
+            &lines,
+        );
+        // On text with several lines, every line is tried for a pattern before the next.
+        assert_same_as_sequential(
+            &mut grok,
+            &[r"^-$", r"^%{INT:n}$", r"^%{WORD:w}$"],
+            &["-x\nabc\n12", "-x\n12\nabc", "-x\n!"],
+        );
+        // Patterns without `^` end a run of merged patterns.
+        assert_same_as_sequential(
+            &mut grok,
+            &[r"^%{INT:n};", r"%{WORD:w}:", r"^%{WORD:x} ", r"^%{INT:y}"],
+            &lines,
+        );
+
+        // The second pattern wins, even though the third one matches an earlier line.
+        let set = grok
+            .compile_set_single_pass([r"^-$", r"^%{INT:n}$", r"^%{WORD:w}$"], true)
+            .expect("Error while compiling!");
+        let found = set.match_first("-x\nabc\n12").unwrap();
+        assert_eq!(1, found.index());
+        assert_eq!(Some("12"), found.matches().get("n"));
+    }
+
+    #[test]
+    fn test_single_pass_unanchored() {
+        let mut grok = Grok::default();
+        let lines = [
+            "abc 12", "12 abc", "abc: 12;", "12; abc:", "1- 2; xa", "!!!",
+        ];
+        // Patterns without `^` are tried one after another.
+        assert_same_as_sequential(&mut grok, &[r"%{INT:n}", r"%{WORD:w}"], &lines);
+        assert_same_as_sequential(
+            &mut grok,
+            &[r"%{INT:n};", r"%{WORD:w}:", r"x%{WORD:x}", r"(?<y>\d+)-"],
+            &lines,
+        );
+
+        // The first pattern wins, even though the second one matches further left.
+        let set = grok
+            .compile_set_single_pass([r"%{INT:n}", r"%{WORD:w}"], true)
+            .expect("Error while compiling!");
+        let found = set.match_first("abc 12").unwrap();
+        assert_eq!(0, found.index());
+        assert_eq!(Some("12"), found.matches().get("n"));
+        assert_eq!(4..6, found.matches().match_span());
+    }
+
+    #[test]
+    fn test_compilation_failure_names_entry() {
+        let mut grok = Grok::default();
+        let err = grok
+            .compile_set(vec!["%{WORD:verb}", "%{NOTDEFINED:x}"], false)
+            .unwrap_err();
+        match err {
+            Error::PatternSetCompilationFailed { index, source } => {
+                assert_eq!(1, index);
+                assert!(matches!(*source, Error::DefinitionsNotFound(_)));
+            }
+            e => panic!("{:?}", e),
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! Suggestions for the names of undefined patterns, based on the edit distance.
+
+/// The maximum number of suggestions for a name.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Returns the names which are closest to the given name, closest first.
+///
+/// Names are compared case-insensitively and only suggested if at most half of the given
+/// name has to be changed, so `IPV4ADDR` suggests `IPV4` but not `HOSTNAME`.
+pub(crate) fn similar_names<'a, I>(name: &str, candidates: I) -> Vec<String>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    let name = name.to_ascii_uppercase();
+    let max_distance = (name.len() / 2).max(1);
+    let mut similar = candidates
+        .into_iter()
+        .map(|c| (edit_distance(&name, &c.to_ascii_uppercase()), c))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .collect::<Vec<_>>();
+    similar.sort();
+    similar
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, c)| c.clone())
+        .collect()
+}
+
+/// Returns the Levenshtein distance of the two strings, counting characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(0, edit_distance("IPV4", "IPV4"));
+        assert_eq!(4, edit_distance("IPV4ADDR", "IPV4"));
+        assert_eq!(2, edit_distance("WROD", "WORD"));
+        assert_eq!(3, edit_distance("", "INT"));
+    }
+
+    #[test]
+    fn test_similar_names() {
+        let names = ["HOSTNAME", "INT", "IPV4", "IPV6", "WORD"]
+            .iter()
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["IPV4"], similar_names("IPV4ADDR", &names));
+        assert_eq!(vec!["IPV4", "IPV6"], similar_names("ipv5", &names));
+        assert_eq!(vec!["WORD"], similar_names("WROD", &names));
+        assert_eq!(Vec::<String>::new(), similar_names("QUOTEDSTRING", &names));
+    }
+}
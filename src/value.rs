@@ -0,0 +1,169 @@
+use crate::Error;
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+/// A typed value of a match, converted according to the type suffix of its alias.
+///
+/// An alias like `%{INT:bytes:int}` produces a `Value::Int`, `:float` a `Value::Float`
+/// and `:bool` a `Value::Bool`. Fields without a type suffix are returned as `Value::Str`.
+/// Converters registered through `Grok::add_converter` may also return a `Value::Custom`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value<'a> {
+    /// The matched text as-is.
+    Str(&'a str),
+    /// A value converted with the `int` suffix.
+    Int(i64),
+    /// A value converted with the `float` suffix.
+    Float(f64),
+    /// A value converted with the `bool` suffix.
+    Bool(bool),
+    /// A value of any other type produced by a custom converter.
+    Custom(Arc<dyn CustomValue>),
+}
+
+impl<'a> Value<'a> {
+    /// Wraps an arbitrary value into a `Value::Custom`.
+    pub fn custom<T: CustomValue>(value: T) -> Self {
+        Value::Custom(Arc::new(value))
+    }
+
+    /// Returns a reference to the inner value if this is a `Value::Custom` of type `T`.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        match *self {
+            Value::Custom(ref c) => (**c).as_any().downcast_ref(),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> fmt::Display for Value<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Str(s) => write!(f, "{}", s),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Float(v) => write!(f, "{}", v),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Custom(ref c) => write!(f, "{}", c),
+        }
+    }
+}
+
+/// A value produced by a custom converter, see `Value::Custom`.
+///
+/// This trait is implemented for every type which is `Debug`, `Display` and `PartialEq`,
+/// so it usually does not need to be implemented by hand.
+pub trait CustomValue: Any + fmt::Debug + fmt::Display + Send + Sync {
+    /// Returns the value as `Any`, which allows to downcast it to its concrete type.
+    fn as_any(&self) -> &dyn Any;
+
+    /// Compares this value with another custom value.
+    fn eq_custom(&self, other: &dyn CustomValue) -> bool;
+}
+
+impl<T> CustomValue for T
+where
+    T: Any + fmt::Debug + fmt::Display + PartialEq + Send + Sync,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_custom(&self, other: &dyn CustomValue) -> bool {
+        (*other).as_any().downcast_ref::<T>() == Some(self)
+    }
+}
+
+impl PartialEq for dyn CustomValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.eq_custom(other)
+    }
+}
+
+/// A `Converter` turns the matched text of a field into a typed `Value`.
+///
+/// Converters are registered by name on `Grok` and referenced as the type suffix of an
+/// alias, for example `%{IP:client:ip}`. The trait is implemented for all closures with
+/// a matching signature.
+pub trait Converter: Send + Sync {
+    /// Converts the matched text, returning the reason as an error if it is not valid.
+    fn convert(&self, value: &str) -> Result<Value<'static>, String>;
+}
+
+impl<F> Converter for F
+where
+    F: Fn(&str) -> Result<Value<'static>, String> + Send + Sync,
+{
+    fn convert(&self, value: &str) -> Result<Value<'static>, String> {
+        self(value)
+    }
+}
+
+impl fmt::Debug for dyn Converter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Converter")
+    }
+}
+
+/// Returns the converters every `Grok` instance starts with.
+pub(crate) fn builtin_converters() -> Vec<(&'static str, Arc<dyn Converter>)> {
+    vec![
+        ("int", Arc::new(convert_int)),
+        ("float", Arc::new(convert_float)),
+        ("bool", Arc::new(convert_bool)),
+    ]
+}
+
+fn convert_int(value: &str) -> Result<Value<'static>, String> {
+    value.parse().map(Value::Int).map_err(|e| e.to_string())
+}
+
+fn convert_float(value: &str) -> Result<Value<'static>, String> {
+    value.parse().map(Value::Float).map_err(|e| e.to_string())
+}
+
+fn convert_bool(value: &str) -> Result<Value<'static>, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "t" | "yes" | "y" | "1" => Ok(Value::Bool(true)),
+        "false" | "f" | "no" | "n" | "0" => Ok(Value::Bool(false)),
+        _ => Err("not a boolean".into()),
+    }
+}
+
+/// The converter resolved for a field when compiling a `Pattern`.
+#[derive(Clone, Debug)]
+pub(crate) struct Conversion {
+    name: String,
+    converter: Arc<dyn Converter>,
+}
+
+impl Conversion {
+    pub(crate) fn new(name: String, converter: Arc<dyn Converter>) -> Self {
+        Conversion { name, converter }
+    }
+
+    /// The name under which the converter has been registered.
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Converts the value of the given field, or returns it as-is if there is no conversion.
+    pub(crate) fn apply<'a>(
+        conversion: Option<&Conversion>,
+        field: &str,
+        value: &'a str,
+    ) -> Result<Value<'a>, Error> {
+        match conversion {
+            Some(c) => c
+                .converter
+                .convert(value)
+                .map_err(|reason| Error::ConversionFailed {
+                    field: field.into(),
+                    value: value.into(),
+                    conversion: c.name.clone(),
+                    reason,
+                }),
+            None => Ok(Value::Str(value)),
+        }
+    }
+}